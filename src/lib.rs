@@ -1,8 +1,15 @@
 mod macros;
 mod render;
+#[cfg(test)]
+mod tests;
 
 use core::fmt::Arguments;
 use std::{
+    cell::RefCell,
+    collections::{
+        HashMap,
+        HashSet,
+    },
     error::Error,
     fs::File,
     io,
@@ -14,17 +21,30 @@ use std::{
 };
 
 use chrono::{
+    format::StrftimeItems,
     Datelike,
     Duration,
     NaiveDate,
     Weekday,
 };
-use clap::Parser;
+use clap::{
+    Parser,
+    ValueEnum,
+};
 use easy_error::{
     bail,
     ResultExt,
 };
-use rand::Rng;
+use flate2::read::GzDecoder;
+use rand::{
+    rngs::StdRng,
+    Rng,
+    SeedableRng,
+};
+use render::{
+    classes,
+    colors,
+};
 use serde::{
     Deserialize,
     Serialize,
@@ -33,19 +53,37 @@ use svg::{
     node::{
         element::{
             path::Data,
+            Circle,
+            Definitions,
+            Description,
+            Element,
             Group,
+            Image,
             Line,
+            LinearGradient,
             Path,
             Rectangle,
+            Stop,
             Style,
+            TSpan,
             Text,
+            Title,
         },
         Blob,
+        Text as TextNode,
     },
     Document,
     Node,
 };
 
+static PAGE_MARGIN: f32 = 10.0; // millimeters
+static NOTE_CHAR_WIDTH: f32 = 7.0; // roughly the widest average 9pt Arial character
+static ITEM_CHAR_WIDTH: f32 = 9.0; // likewise for 12pt
+static ITEM_LINE_HEIGHT: f32 = 20.0;
+static STATUS_WIDTH: f32 = 12.0;
+static UTILIZATION_STEP: f32 = 6.0; // the height of one task in the utilization band
+static ARROW_GAP: f32 = 5.0; // how far a dependency arrow comes out of a bar before turning
+static INKSCAPE_NAMESPACE: &str = "http://www.inkscape.org/namespaces/inkscape";
 static GOLDEN_RATIO_CONJUGATE: f32 = 0.618034; // 0.618033988749895
 static MONTH_NAMES: [&str; 12] = [
     "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
@@ -54,14 +92,36 @@ static MONTH_NAMES: [&str; 12] = [
 #[derive(Parser)]
 #[clap(version, about, long_about = None)]
 struct Cli {
-    /// Specify the JSON data file
+    /// Specify the JSON5, TOML or CSV data file, or an http(s) URL to fetch it from
     #[arg(value_name = "INPUT_FILE")]
     input_file: Option<PathBuf>,
 
-    /// The SVG output file
+    /// The SVG output file, or another input file when writing to an output directory
     #[arg(value_name = "OUTPUT_FILE")]
     output_file: Option<PathBuf>,
 
+    /// More input files, when writing to an output directory
+    #[arg(value_name = "INPUT_FILE", requires = "output_dir")]
+    more_input_files: Vec<PathBuf>,
+
+    /// Render every input file, or every chart file in an input directory, to a file of the same name in this directory
+    #[arg(value_name = "DIR", long)]
+    output_dir: Option<PathBuf>,
+
+    /// Stop at the first file that cannot be rendered, instead of going on to the rest
+    #[arg(long, default_value_t = false, requires = "output_dir")]
+    fail_fast: bool,
+
+    /// Write the chart in several forms named after the output file, e.g. svg,png@1x,png@2x for chart.svg, chart.png and chart@2x.png
+    #[arg(
+        value_name = "LIST",
+        long,
+        value_delimiter = ',',
+        requires = "output_file",
+        conflicts_with = "output_dir"
+    )]
+    emit: Vec<String>,
+
     /// The width of the item title column
     #[arg(value_name = "WIDTH", short, long, default_value_t = 210.0)]
     title_width: f32,
@@ -70,395 +130,3486 @@ struct Cli {
     #[arg(value_name = "WIDTH", short, long, default_value_t = 200.0)]
     max_month_width: f32,
 
+    /// Give every day the same width instead, so that charts of any length share a scale
+    #[arg(
+        value_name = "WIDTH",
+        long,
+        conflicts_with_all = ["max_month_width", "max_width", "uniform_months"]
+    )]
+    px_per_day: Option<f32>,
+
+    /// The maximum width of the document, month widths are reduced to fit and text may get crowded
+    #[arg(value_name = "WIDTH", long)]
+    max_width: Option<f32>,
+
+    /// The narrowest a task bar is drawn, so short tasks stay visible at the cost of looking a little longer
+    #[arg(value_name = "WIDTH", long, default_value_t = 1.0)]
+    min_bar_width: f32,
+
+    /// The corner radius of the task bars, zero for square corners
+    #[arg(value_name = "RADIUS", long, default_value_t = 3.0)]
+    corner_radius: f32,
+
+    /// The space above the title
+    #[arg(value_name = "WIDTH", long, default_value_t = 0.0)]
+    margin_top: f32,
+
+    /// The space to the left of the chart
+    #[arg(value_name = "WIDTH", long, default_value_t = 10.0)]
+    margin_left: f32,
+
+    /// The space to the right of the chart
+    #[arg(value_name = "WIDTH", long, default_value_t = 10.0)]
+    margin_right: f32,
+
+    /// The space below the chart
+    #[arg(value_name = "WIDTH", long, default_value_t = 10.0)]
+    margin_bottom: f32,
+
     /// Add a resource table at the bottom of the graph
     #[arg(short, long, default_value_t = false)]
     legend: bool,
-}
 
-impl Cli {
-    fn get_output(&self) -> Result<Box<dyn Write>, Box<dyn Error>> {
-        match self.output_file {
-            Some(ref path) => File::create(path)
-                .context(format!(
-                    "Unable to create file '{}'",
-                    path.to_string_lossy()
-                ))
-                .map(|f| Box::new(f) as Box<dyn Write>)
-                .map_err(|e| Box::new(e) as Box<dyn Error>),
-            None => Ok(Box::new(io::stdout())),
-        }
-    }
+    /// Where to put the resource table, with `top` fitting it beside the title; `--legend` puts it at the bottom
+    #[arg(value_enum, long)]
+    legend_position: Option<LegendPosition>,
 
-    fn get_input(&self) -> Result<Box<dyn Read>, Box<dyn Error>> {
-        match self.input_file {
-            Some(ref path) => File::open(path)
-                .context(format!("Unable to open file '{}'", path.to_string_lossy()))
-                .map(|f| Box::new(f) as Box<dyn Read>)
-                .map_err(|e| Box::new(e) as Box<dyn Error>),
-            None => Ok(Box::new(io::stdin())),
-        }
-    }
-}
+    /// Add the number of tasks and days for each resource to the resource table
+    #[arg(long, default_value_t = false)]
+    legend_stats: bool,
 
-pub trait GanttChartLog {
-    fn output(&self, args: Arguments);
-    fn warning(&self, args: Arguments);
-    fn error(&self, args: Arguments);
-}
+    /// Lay the chart out right-to-left
+    #[arg(long, default_value_t = false)]
+    rtl: bool,
 
-pub struct GanttChartTool<'a> {
-    log: &'a dyn GanttChartLog,
-}
+    /// Omit the title column and show the titles on the task bars instead
+    #[arg(short, long, default_value_t = false)]
+    inline_titles: bool,
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct ItemData {
-    pub title: String,
-    pub duration: Option<i64>,
-    #[serde(rename = "startDate", skip_serializing_if = "Option::is_none")]
-    pub start_date: Option<NaiveDate>,
-    #[serde(rename = "resource")]
-    pub resource_index: Option<usize>,
-    pub open: Option<bool>,
-}
+    /// Wrap long titles onto more lines to fit the title column, making the rows taller
+    #[arg(long, default_value_t = false, conflicts_with_all = ["inline_titles", "milestones_only"])]
+    wrap_titles: bool,
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct ChartData {
-    pub title: String,
-    #[serde(rename = "markedDate")]
-    pub marked_date: Option<NaiveDate>,
-    pub resources: Vec<String>,
-    pub items: Vec<ItemData>,
-}
+    /// Cut titles longer than this down to size with an ellipsis, showing the whole title as a tooltip
+    #[arg(value_name = "CHARS", long, conflicts_with = "wrap_titles")]
+    max_title_chars: Option<usize>,
 
-#[derive(Debug)]
-pub struct Gutter {
-    left: f32,
-    top: f32,
-    right: f32,
-    bottom: f32,
-}
+    /// Show a band above the rows for each group of items, across the dates of the group
+    #[arg(long, default_value_t = false)]
+    phase_bands: bool,
 
-impl Gutter {
-    pub fn height(&self) -> f32 {
-        self.bottom + self.top
-    }
+    /// Show how many tasks each resource has on each day, stacked in a band under the chart
+    #[arg(long, default_value_t = false)]
+    utilization: bool,
 
-    pub fn width(&self) -> f32 {
-        self.right + self.left
-    }
-}
+    /// Leave out the tasks for a compact timeline of just the milestones, in one band with their titles beside them
+    #[arg(long, default_value_t = false)]
+    milestones_only: bool,
 
-#[derive(Debug)]
-struct RenderData {
-    title: String,
-    gutter: Gutter,
-    row_gutter: Gutter,
-    row_height: f32,
-    resource_gutter: Gutter,
-    resource_height: f32,
-    marked_date_offset: Option<f32>,
-    title_width: f32,
-    max_month_width: f32,
-    rect_corner_radius: f32,
-    styles: Vec<String>,
-    cols: Vec<ColumnRenderData>,
-    rows: Vec<RowRenderData>,
-    resources: Vec<String>,
-}
+    /// Shade every other row to make them easier to follow
+    #[arg(short, long, default_value_t = false)]
+    zebra: bool,
 
-#[derive(Debug)]
-struct RowRenderData {
-    title: String,
-    resource_index: usize,
-    offset: f32,
-    // If length not present then this is a milestone
-    length: Option<f32>,
-    open: bool,
-}
+    /// Tint the rows of each resource with a light version of its color
+    #[arg(long, default_value_t = false, conflicts_with = "zebra")]
+    swimlanes: bool,
 
-#[derive(Debug)]
-struct ColumnRenderData {
-    width: f32,
-    month_name: String,
-}
+    /// Stretch items without a duration or start date up to the next item with a start date, instead of making them milestones
+    #[arg(long, default_value_t = false)]
+    autofill_durations: bool,
 
-impl<'a> GanttChartTool<'a> {
-    pub fn new(log: &'a dyn GanttChartLog) -> GanttChartTool {
-        GanttChartTool { log }
-    }
+    /// Add this many calendar days of empty runway before the first month, unless there is a window
+    #[arg(value_name = "DAYS", long, default_value_t = 0)]
+    lead_days: u32,
 
-    pub fn run(
-        &mut self,
-        args: impl IntoIterator<Item = std::ffi::OsString>,
-    ) -> Result<(), Box<dyn Error>> {
-        let cli = match Cli::try_parse_from(args) {
-            Ok(cli) => cli,
-            Err(err) => {
-                output!(self.log, "{}", err.to_string());
-                return Ok(());
-            }
-        };
+    /// Make every month the same width, whatever its number of days
+    #[arg(long, default_value_t = false)]
+    uniform_months: bool,
 
-        let chart_data = Self::read_chart_file(cli.get_input()?)?;
-        let render_data =
-            self.process_chart_data(cli.title_width, cli.max_month_width, &chart_data)?;
-        let output = self.render_chart(cli.legend, &render_data)?;
+    /// Give the weekends no width, so the timeline only shows working days
+    #[arg(long, default_value_t = false, conflicts_with = "weekend_bands")]
+    compress_weekends: bool,
 
-        Self::write_svg_file(cli.get_output()?, &output)?;
-        Ok(())
-    }
+    /// Count the weekends as working days, so that durations are in calendar days and nothing is moved off a weekend
+    #[arg(
+        long,
+        alias = "no-weekend-adjust",
+        default_value_t = false,
+        conflicts_with = "compress_weekends"
+    )]
+    calendar_days: bool,
 
-    fn read_chart_file(mut reader: Box<dyn Read>) -> Result<ChartData, Box<dyn Error>> {
-        let mut content = String::new();
+    /// Shade the weekends behind the task bars
+    #[arg(long, default_value_t = false)]
+    weekend_bands: bool,
 
-        reader.read_to_string(&mut content)?;
+    /// Mark the weekend days under the month headings
+    #[arg(long, default_value_t = false, conflicts_with = "compress_weekends")]
+    show_weekends_in_header: bool,
 
-        let chart_data: ChartData = json5::from_str(&content)?;
+    /// Draw a line at the start of every week
+    #[arg(long, default_value_t = false)]
+    week_lines: bool,
 
-        Ok(chart_data)
-    }
+    /// Label the week lines with their week numbers, ISO for Monday weeks and US for Sunday weeks
+    #[arg(long, default_value_t = false, requires = "week_lines")]
+    week_numbers: bool,
 
-    fn write_svg_file(mut writer: Box<dyn Write>, output: &str) -> Result<(), Box<dyn Error>> {
-        write!(writer, "{}", output)?;
+    /// The strftime format of any dates shown in the chart
+    #[arg(value_name = "FORMAT", long, default_value = "%Y-%m-%d")]
+    date_format: String,
 
-        Ok(())
-    }
+    /// A CSS rule to add to the chart styles, e.g. '.late{fill:red;}' for items with the class 'late'
+    #[arg(value_name = "RULE", long)]
+    extra_style: Vec<String>,
 
-    fn hsv_to_rgb(h: f32, s: f32, v: f32) -> u32 {
-        let h_i = (h * 6.0) as usize;
-        let f = h * 6.0 - h_i as f32;
-        let p = v * (1.0 - s);
-        let q = v * (1.0 - f * s);
-        let t = v * (1.0 - (1.0 - f) * s);
+    /// The saturation of the generated resource colors, from 0 to 1
+    #[arg(value_name = "AMOUNT", long, default_value_t = 0.5)]
+    color_saturation: f32,
 
-        fn rgb(r: f32, g: f32, b: f32) -> u32 {
-            ((r * 256.0) as u32) << 16 | ((g * 256.0) as u32) << 8 | ((b * 256.0) as u32)
-        }
+    /// The brightness of the generated resource colors, from 0 to 1
+    #[arg(value_name = "AMOUNT", long, default_value_t = 0.5)]
+    color_value: f32,
 
-        if h_i == 0 {
-            rgb(v, t, p)
-        } else if h_i == 1 {
-            rgb(q, v, p)
-        } else if h_i == 2 {
-            rgb(p, v, t)
-        } else if h_i == 3 {
-            rgb(p, q, v)
-        } else if h_i == 4 {
-            rgb(t, p, v)
-        } else {
-            rgb(v, p, q)
-        }
-    }
+    /// The hue of the first resource color, from 0 up to 1, instead of a random one
+    #[arg(value_name = "HUE", long)]
+    hue_start: Option<f32>,
 
-    fn process_chart_data(
-        &self,
-        title_width: f32,
-        max_month_width: f32,
-        chart_data: &ChartData,
-    ) -> Result<RenderData, Box<dyn Error>> {
-        fn num_days_in_month(year: i32, month: u32) -> u32 {
-            // the first day of the next month...
-            let (y, m) = if month == 12 {
-                (year + 1, 1)
-            } else {
-                (year, month + 1)
-            };
-            let d = NaiveDate::from_ymd_opt(y, m, 1).unwrap(); // FIXME unwrap
+    /// Seed the random resource colors, so they are the same every time
+    #[arg(value_name = "SEED", long)]
+    seed: Option<u64>,
 
-            // ...is preceded by the last day of the original month
-            d.pred_opt().unwrap().day() // FIXME unwrap
-        }
+    /// The first day of the week, for the week lines and numbers
+    #[arg(value_enum, long, default_value_t = WeekStart::Mon)]
+    week_start: WeekStart,
 
-        // Fail if only one task
-        if chart_data.items.len() < 2 {
-            bail!("You must provide more than one task");
-        }
+    /// How the closed task bars are filled
+    #[arg(value_enum, long, default_value_t = BarStyle::Flat)]
+    bar_style: BarStyle,
 
-        let mut start_date = NaiveDate::MAX;
-        let mut end_date = NaiveDate::MIN;
-        let mut date = NaiveDate::MIN;
-        let mut shadow_durations: Vec<Option<i64>> = Vec::with_capacity(chart_data.items.len());
+    /// The shape of the milestone markers
+    #[arg(value_enum, long, default_value_t = MilestoneShape::Diamond)]
+    milestone_shape: MilestoneShape,
 
-        // Determine the project start & end dates
-        for (i, item) in chart_data.items.iter().enumerate() {
-            if let Some(item_start_date) = item.start_date {
-                date = item_start_date;
+    /// Scale the milestone markers relative to the row height
+    #[arg(value_name = "FACTOR", long, default_value_t = 1.0)]
+    milestone_size: f32,
 
-                if item_start_date < start_date {
-                    // Move the start if it falls on a weekend
-                    start_date = match date.weekday() {
-                        Weekday::Sat => date + Duration::try_days(2).unwrap(), // FIXME unwrap
-                        Weekday::Sun => date + Duration::try_days(1).unwrap(), // FIXME unwrap
-                        _ => date,
-                    };
-                }
-            } else if i == 0 {
-                return Err(From::from(
-                    "First item must contain a start date".to_string(),
-                ));
-            }
+    /// Draw guide lines through each row at the start and end of its bar
+    #[arg(long, default_value_t = false)]
+    task_guides: bool,
 
-            // Skip the weekends and update a shadow list of the _real_ durations
-            if let Some(item_days) = item.duration {
-                // FIXME unwrap
-                let duration = match (date + Duration::try_days(item_days).unwrap()).weekday() {
-                    Weekday::Sat => Duration::try_days(item_days + 2).unwrap(),
-                    Weekday::Sun => Duration::try_days(item_days + 1).unwrap(),
-                    _ => Duration::try_days(item_days).unwrap(),
-                };
+    /// Add a row at the bottom showing the overall completion of the tasks
+    #[arg(long, default_value_t = false)]
+    summary: bool,
 
-                date += duration;
+    /// Draw the baseline of each item under its bar, to show how far it has drifted from the plan
+    #[arg(long, default_value_t = false)]
+    baseline: bool,
 
-                shadow_durations.push(Some(duration.num_days()));
-            } else {
-                shadow_durations.push(None);
-            }
+    /// Draw an arrow to each item without a start date from the item before it, which is what it follows on from
+    #[arg(long, default_value_t = false)]
+    arrows: bool,
 
-            if end_date < date {
-                end_date = date;
-            }
+    /// Show how many calendar and working days the project takes, across from the title
+    #[arg(long, default_value_t = false)]
+    show_duration: bool,
 
-            if let Some(item_resource_index) = item.resource_index {
-                if item_resource_index >= chart_data.resources.len() {
-                    return Err(From::from("Resource index is out of range".to_string()));
-                }
-            } else if i == 0 {
-                return Err(From::from(
-                    "First item must contain a resource index".to_string(),
-                ));
-            }
-        }
+    /// Leave out the line between the title column and the timeline
+    #[arg(long, default_value_t = false)]
+    no_title_divider: bool,
 
-        start_date = NaiveDate::from_ymd_opt(start_date.year(), start_date.month(), 1).unwrap(); // FIXME unwrap
-        end_date = NaiveDate::from_ymd_opt(
-            end_date.year(),
-            end_date.month(),
-            num_days_in_month(end_date.year(), end_date.month()),
-        )
-        .unwrap(); // FIXME unwrap
+    /// Leave out the "Tasks" heading over the title column
+    #[arg(long, default_value_t = false)]
+    no_task_header: bool,
 
-        // Create all the column data
-        let mut all_items_width: f32 = 0.0;
-        let mut num_item_days: u32 = 0;
-        let mut cols = vec![];
+    /// Extend each task that is not critical by how far it can slip, up to the next item if that has no start date and so follows on from it, otherwise up to the project end
+    #[arg(long, default_value_t = false)]
+    show_slack: bool,
 
-        date = start_date;
+    /// Label the project start and end dates under the timeline
+    #[arg(long, default_value_t = false)]
+    show_bounds: bool,
 
-        while date <= end_date {
-            let item_days = num_days_in_month(date.year(), date.month());
-            let item_width = max_month_width * (item_days as f32) / 31.0;
+    /// Where to put the title and subtitle across the chart, otherwise at the start of the reading direction
+    #[arg(value_enum, long)]
+    title_align: Option<TitleAlign>,
 
-            num_item_days += item_days;
-            all_items_width += item_width;
+    /// Highlight the chain of tasks that determines the project end date
+    #[arg(long, default_value_t = false)]
+    critical_path: bool,
 
-            cols.push(ColumnRenderData {
-                width: item_width,
-                month_name: MONTH_NAMES[date.month() as usize - 1].to_string(),
-            });
+    /// Uniformly scale the output document
+    #[arg(value_name = "SCALE", short, long, default_value_t = 1.0)]
+    scale: f32,
 
-            date = NaiveDate::from_ymd_opt(
-                date.year() + (if date.month() == 12 { 1 } else { 0 }),
-                date.month() % 12 + 1,
-                1,
-            )
-            .unwrap(); // FIXME unwrap
-        }
+    /// Size the document to a page, fitting the chart inside the margins
+    #[arg(value_enum, long, ignore_case = true, conflicts_with_all = ["scale", "fragment"])]
+    page: Option<PageSize>,
 
-        date = start_date;
+    /// Use landscape rather than portrait page orientation
+    #[arg(long, default_value_t = false, requires = "page")]
+    landscape: bool,
 
-        let mut resource_index: usize = 0;
-        let gutter = Gutter {
-            left: 10.0,
-            top: 80.0,
-            right: 10.0,
-            bottom: 10.0,
-        };
-        let row_gutter = Gutter {
-            left: 5.0,
-            top: 5.0,
-            right: 5.0,
-            bottom: 5.0,
-        };
-        // TODO(john): The 20.0 should be configurable, and for the resource table
-        let row_height = row_gutter.height() + 20.0;
-        let resource_gutter = Gutter {
-            left: 10.0,
-            top: 10.0,
-            right: 10.0,
-            bottom: 10.0,
-        };
-        let resource_height = resource_gutter.height() + 20.0;
-        let mut rows = vec![];
+    /// Center the chart on the page rather than at the top left
+    #[arg(long, default_value_t = false, requires = "page")]
+    page_center: bool,
 
-        // Calculate the X offsets of all the bars and milestones
-        for (i, item) in chart_data.items.iter().enumerate() {
-            if let Some(item_start_date) = item.start_date {
-                date = item_start_date;
-            }
+    /// Cap the document height, showing a window onto a taller chart
+    #[arg(value_name = "HEIGHT", long, conflicts_with_all = ["page", "fragment"])]
+    max_height: Option<f32>,
 
-            let offset = title_width
-                + gutter.left
-                + ((date - start_date).num_days() as f32) / (num_item_days as f32)
-                    * all_items_width;
+    /// The row to bring to the top of the rows in the window when the height is capped
+    #[arg(value_name = "ROW", long, default_value_t = 0, requires = "max_height")]
+    scroll_to: usize,
 
-            let mut length: Option<f32> = None;
+    /// Embed the chart data as JSON in the SVG metadata, so the chart can be regenerated
+    #[arg(long, default_value_t = false)]
+    embed_source: bool,
 
-            if let Some(item_days) = shadow_durations[i] {
-                // Use the shadow duration instead of the actual duration as it accounts for weekends
-                date += Duration::try_days(item_days).unwrap(); // FIXME unwrap
-                length = Some((item_days as f32) / (num_item_days as f32) * all_items_width);
-            }
+    /// Round the positions and sizes to whole pixels, and thin lines to half pixels, for crisper edges when rasterized
+    #[arg(long, default_value_t = false)]
+    snap_pixels: bool,
 
-            if let Some(item_resource_index) = item.resource_index {
-                resource_index = item_resource_index;
-            }
+    /// Output only the chart groups and styles in one transformed group, for embedding in another SVG
+    #[arg(long, default_value_t = false)]
+    fragment: bool,
 
-            rows.push(RowRenderData {
-                title: item.title.clone(),
-                resource_index,
-                offset,
-                length,
-                open: item.open.unwrap_or(false),
-            });
-        }
+    /// The number of decimal places in the positions and sizes, so small changes do not show in diffs
+    #[arg(value_name = "DIGITS", long, default_value_t = 2)]
+    precision: usize,
 
-        let marked_date_offset = chart_data.marked_date.map(|date| {
-            title_width
+    /// A prefix for all the style class names, to avoid clashes when inlined in a page
+    #[arg(
+        value_name = "PREFIX",
+        long,
+        alias = "style-prefix",
+        default_value = ""
+    )]
+    class_prefix: String,
+
+    /// The order of the rows, scheduling always uses the order of the input file
+    #[arg(value_enum, long, default_value_t = RowOrder::None)]
+    sort: RowOrder,
+
+    /// The output format
+    #[arg(value_enum, short, long, default_value_t = OutputFormat::Svg)]
+    format: OutputFormat,
+
+    /// Write a web page showing the chart, the same as --format html
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    html: bool,
+
+    /// Check the chart data for problems without writing any output
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// Mark the finished tasks as done and color the unfinished ones past their end date, from their percent complete
+    #[arg(long, default_value_t = false)]
+    auto_status: bool,
+
+    /// The date that tasks are overdue after with --auto-status, otherwise the marked date or the current date
+    #[arg(value_name = "DATE", long, requires = "auto_status")]
+    today: Option<NaiveDate>,
+
+    /// Only show the dates from START to END, e.g. 2022-07-01..2022-09-30, cutting off the items at the edges
+    #[arg(value_name = "START..END", long)]
+    window: Option<String>,
+
+    /// Do not warn about items in the same group that use different resources
+    #[arg(long, default_value_t = false)]
+    allow_mixed_groups: bool,
+
+    /// Print the computed layout to stderr instead of writing any output, in a format given with `=FORMAT`
+    #[arg(
+        value_enum,
+        value_name = "FORMAT",
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "debug"
+    )]
+    dump_layout: Option<LayoutFormat>,
+
+    /// Print the chart style rules, one per line, instead of writing any output
+    #[arg(long, default_value_t = false, conflicts_with = "dump_layout")]
+    dump_styles: bool,
+
+    /// Write all the warnings and errors as JSON to a file given with `=FILE`, or to stderr if no file is given
+    #[arg(
+        value_name = "FILE",
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-"
+    )]
+    diagnostics_json: Option<PathBuf>,
+
+    /// Do not print any warnings
+    #[arg(short, long, default_value_t = false)]
+    quiet: bool,
+
+    /// Fail if there are any warnings, as well as on errors
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// SVG image of the chart
+    Svg,
+    /// JSON description of the computed schedule
+    Json,
+    /// Web page with the SVG image inside it
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LayoutFormat {
+    /// Rust debug formatting
+    Debug,
+    /// JSON
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InputFormat {
+    Json5,
+    Toml,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PageSize {
+    A4,
+    Letter,
+    Legal,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum WeekStart {
+    Sun,
+    Mon,
+}
+
+impl WeekStart {
+    fn weekday(&self) -> Weekday {
+        match self {
+            WeekStart::Sun => Weekday::Sun,
+            WeekStart::Mon => Weekday::Mon,
+        }
+    }
+
+    // US weeks are counted from the one containing January 1st
+    fn week_number(&self, date: NaiveDate) -> u32 {
+        match self {
+            WeekStart::Sun => {
+                let jan_1 = NaiveDate::from_yo_opt(date.year(), 1).unwrap(); // FIXME unwrap
+
+                (date.ordinal0() + jan_1.weekday().num_days_from_sunday()) / 7 + 1
+            }
+            WeekStart::Mon => date.iso_week().week(),
+        }
+    }
+}
+
+impl PageSize {
+    // Width and height in millimeters
+    fn size(&self, landscape: bool) -> (f32, f32) {
+        let (width, height) = match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::Legal => (215.9, 355.6),
+        };
+
+        if landscape {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum BarStyle {
+    /// Fill the bars with the resource color
+    Flat,
+    /// Fill the bars with a gradient from a lighter shade down to the resource color
+    Gradient,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MilestoneShape {
+    Diamond,
+    Circle,
+    Star,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LegendPosition {
+    /// A compact line of colors and names beside the title
+    Top,
+    /// A table of the resources under the rows
+    Bottom,
+    /// No resource table
+    None,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TitleAlign {
+    Left,
+    Center,
+    Right,
+}
+
+// One of the files written with --emit, with the scale of a PNG image
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Emit {
+    Svg,
+    Png(f32),
+}
+
+impl Emit {
+    // The output file itself for SVG, otherwise the same name with a suffix for the scale
+    fn path(&self, output_file: &std::path::Path) -> PathBuf {
+        let stem = output_file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let file_name = match self {
+            Emit::Svg => format!("{stem}.svg"),
+            Emit::Png(scale) if *scale == 1.0 => format!("{stem}.png"),
+            Emit::Png(scale) => format!("{stem}@{scale}x.png"),
+        };
+
+        output_file.with_file_name(file_name)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum RowOrder {
+    /// Keep the order of the input file, or of the item order values if given
+    None,
+    /// Order by the computed start date
+    Start,
+    /// Group by resource
+    Resource,
+}
+
+impl Cli {
+    fn get_output(&self) -> Result<Box<dyn Write>, Box<dyn Error>> {
+        match self.output_file {
+            Some(ref path) => File::create(path)
+                .context(format!(
+                    "Unable to create file '{}'",
+                    path.to_string_lossy()
+                ))
+                .map(|f| Box::new(f) as Box<dyn Write>)
+                .map_err(|e| Box::new(e) as Box<dyn Error>),
+            None => Ok(Box::new(io::stdout())),
+        }
+    }
+
+    fn output_format(&self) -> OutputFormat {
+        if self.html {
+            OutputFormat::Html
+        } else {
+            self.format
+        }
+    }
+
+    fn emit_variants(&self) -> Result<Vec<Emit>, Box<dyn Error>> {
+        if !self.emit.is_empty() && self.output_format() != OutputFormat::Svg {
+            bail!("Only the SVG chart can be written with --emit");
+        }
+
+        self.emit
+            .iter()
+            .map(|variant| {
+                let scale = match variant.trim().to_ascii_lowercase().as_str() {
+                    "svg" => return Ok(Emit::Svg),
+                    "png" => Some(1.0),
+                    png => png
+                        .strip_prefix("png@")
+                        .and_then(|scale| scale.strip_suffix('x'))
+                        .and_then(|scale| scale.parse::<f32>().ok()),
+                };
+
+                match scale {
+                    Some(scale) if scale.is_finite() && scale > 0.0 => Ok(Emit::Png(scale)),
+                    _ => bail!(
+                        "Unknown output '{}', expected svg, png or png@SCALEx such as png@2x",
+                        variant
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn get_input(input_file: Option<&std::path::Path>) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        match input_file {
+            Some(path) if Self::is_url(path) => {
+                let url = path.to_string_lossy();
+
+                ureq::get(&url)
+                    .call()
+                    .context(format!("Unable to fetch URL '{}'", url))
+                    .map(|response| response.into_reader() as Box<dyn Read>)
+                    .map_err(|e| Box::new(e) as Box<dyn Error>)
+            }
+            Some(path) if Self::is_gzip(path) => File::open(path)
+                .context(format!("Unable to open file '{}'", path.to_string_lossy()))
+                .map(|f| Box::new(GzDecoder::new(f)) as Box<dyn Read>)
+                .map_err(|e| Box::new(e) as Box<dyn Error>),
+            Some(path) => File::open(path)
+                .context(format!("Unable to open file '{}'", path.to_string_lossy()))
+                .map(|f| Box::new(f) as Box<dyn Read>)
+                .map_err(|e| Box::new(e) as Box<dyn Error>),
+            None => Ok(Box::new(io::stdin())),
+        }
+    }
+
+    fn is_gzip(path: &std::path::Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "gz")
+    }
+
+    // TOML files are recognized by their extension, anything else is JSON5, looking inside a .gz extension
+    fn get_input_format(input_file: Option<&std::path::Path>) -> InputFormat {
+        let inner_file = input_file
+            .filter(|path| Self::is_gzip(path))
+            .map(|path| path.with_extension(""));
+
+        match inner_file.as_deref().or(input_file) {
+            Some(path) if path.extension().is_some_and(|ext| ext == "toml") => InputFormat::Toml,
+            Some(path) if path.extension().is_some_and(|ext| ext == "csv") => InputFormat::Csv,
+            _ => InputFormat::Json5,
+        }
+    }
+
+    // Directories are expanded to the chart files in them, in name order
+    fn get_batch_inputs(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut inputs = vec![];
+
+        for path in self
+            .input_file
+            .iter()
+            .chain(self.output_file.iter())
+            .chain(self.more_input_files.iter())
+        {
+            if !path.is_dir() {
+                inputs.push(path.clone());
+                continue;
+            }
+
+            let mut dir_inputs = vec![];
+
+            for entry in std::fs::read_dir(path).context(format!(
+                "Unable to read directory '{}'",
+                path.to_string_lossy()
+            ))? {
+                let entry_path = entry?.path();
+
+                if entry_path.extension().is_some_and(|ext| {
+                    ["json5", "json", "toml", "csv", "gz"]
+                        .iter()
+                        .any(|chart_ext| ext == *chart_ext)
+                }) {
+                    dir_inputs.push(entry_path);
+                }
+            }
+
+            dir_inputs.sort();
+            inputs.extend(dir_inputs);
+        }
+
+        if inputs.is_empty() {
+            bail!("No input files to write to the output directory");
+        }
+
+        Ok(inputs)
+    }
+
+    fn is_url(path: &std::path::Path) -> bool {
+        path.to_str()
+            .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+    }
+
+    fn layout_options(&self) -> Result<LayoutOptions, Box<dyn Error>> {
+        if !self.corner_radius.is_finite() || self.corner_radius < 0.0 {
+            bail!("Corner radius must not be negative");
+        }
+
+        if [
+            self.margin_top,
+            self.margin_left,
+            self.margin_right,
+            self.margin_bottom,
+        ]
+        .iter()
+        .any(|margin| !margin.is_finite() || *margin < 0.0)
+        {
+            bail!("Margins must not be negative");
+        }
+
+        if !self.min_bar_width.is_finite() || self.min_bar_width < 0.0 {
+            bail!("Minimum bar width must not be negative");
+        }
+
+        if self
+            .px_per_day
+            .is_some_and(|px_per_day| !px_per_day.is_finite() || px_per_day <= 0.0)
+        {
+            bail!("Pixels per day must be a positive number");
+        }
+
+        if StrftimeItems::new(&self.date_format).any(|item| item == chrono::format::Item::Error) {
+            bail!("Date format '{}' is not valid", self.date_format);
+        }
+
+        let window = match self.window {
+            Some(ref window) => {
+                let Some((start, end)) = window.split_once("..") else {
+                    bail!("Window '{}' must be given as START..END", window);
+                };
+                let start = start
+                    .parse::<NaiveDate>()
+                    .context(format!("Window start '{}' is not a valid date", start))?;
+                let end = end
+                    .parse::<NaiveDate>()
+                    .context(format!("Window end '{}' is not a valid date", end))?;
+
+                if end < start {
+                    bail!("Window end {} is before its start {}", end, start);
+                }
+
+                Some((start, end))
+            }
+            None => None,
+        };
+
+        if !(0.0..=1.0).contains(&self.color_saturation) {
+            bail!("Color saturation must be between 0 and 1");
+        }
+
+        if !(0.0..=1.0).contains(&self.color_value) {
+            bail!("Color value must be between 0 and 1");
+        }
+
+        if self.hue_start.is_some_and(|hue| !(0.0..1.0).contains(&hue)) {
+            bail!("Hue start must be from 0 up to 1");
+        }
+
+        Ok(LayoutOptions {
+            title_width: if self.inline_titles || self.milestones_only {
+                0.0
+            } else {
+                self.title_width
+            },
+            max_month_width: self.max_month_width,
+            px_per_day: self.px_per_day,
+            wrap_titles: self.wrap_titles,
+            milestones_only: self.milestones_only,
+            phase_bands: self.phase_bands,
+            utilization: self.utilization,
+            auto_status: self.auto_status,
+            today: self.today,
+            max_width: self.max_width,
+            corner_radius: self.corner_radius,
+            margins: Gutter {
+                left: self.margin_left,
+                top: self.margin_top,
+                right: self.margin_right,
+                bottom: self.margin_bottom,
+            },
+            min_bar_width: self.min_bar_width,
+            compress_weekends: self.compress_weekends,
+            calendar_days: self.calendar_days,
+            uniform_months: self.uniform_months,
+            lead_days: self.lead_days,
+            autofill_durations: self.autofill_durations,
+            week_start: self.week_start,
+            date_format: self.date_format.clone(),
+            color_saturation: self.color_saturation,
+            color_value: self.color_value,
+            hue_start: self.hue_start,
+            seed: self.seed,
+            extra_styles: self.extra_style.clone(),
+            window,
+        })
+    }
+
+    fn render_options(&self) -> Result<RenderOptions, Box<dyn Error>> {
+        if !self.scale.is_finite() || self.scale <= 0.0 {
+            bail!("Scale must be a positive number");
+        }
+
+        if !self.milestone_size.is_finite() || self.milestone_size <= 0.0 {
+            bail!("Milestone size must be a positive number");
+        }
+
+        if self.max_title_chars == Some(0) {
+            bail!("Maximum title characters must be at least 1");
+        }
+
+        if let Some(max_height) = self.max_height {
+            if !max_height.is_finite() || max_height <= 0.0 {
+                bail!("Maximum height must be a positive number");
+            }
+        }
+
+        Ok(RenderOptions {
+            legend: self.legend_position.unwrap_or(if self.legend {
+                LegendPosition::Bottom
+            } else {
+                LegendPosition::None
+            }),
+            legend_stats: self.legend_stats,
+            rtl: self.rtl,
+            zebra: self.zebra,
+            swimlanes: self.swimlanes,
+            weekend_bands: self.weekend_bands,
+            weekend_marks: self.show_weekends_in_header,
+            week_lines: self.week_lines,
+            week_numbers: self.week_numbers,
+            critical_path: self.critical_path,
+            task_guides: self.task_guides,
+            summary: self.summary,
+            baseline: self.baseline,
+            arrows: self.arrows,
+            show_duration: self.show_duration,
+            show_slack: self.show_slack,
+            title_divider: !self.no_title_divider,
+            task_header: !self.no_task_header,
+            show_bounds: self.show_bounds,
+            title_align: self.title_align,
+            bar_style: self.bar_style,
+            milestone_shape: self.milestone_shape,
+            milestone_size: self.milestone_size,
+            scale: self.scale,
+            page: self.page,
+            max_height: self.max_height,
+            scroll_to: self.scroll_to,
+            max_title_chars: self.max_title_chars,
+            landscape: self.landscape,
+            page_center: self.page_center,
+            fragment: self.fragment,
+            precision: self.precision,
+            class_prefix: self.class_prefix.clone(),
+        })
+    }
+}
+
+pub trait GanttChartLog {
+    fn output(&self, args: Arguments);
+    // For output that is about the chart rather than the chart itself, so it goes to stderr
+    fn debug(&self, _args: Arguments) {}
+    fn warning(&self, args: Arguments);
+    fn error(&self, args: Arguments);
+}
+
+pub struct GanttChartTool<'a> {
+    log: &'a dyn GanttChartLog,
+    quiet: bool,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+    // Makes the month headings from the year and month, instead of the short month names
+    month_name: Option<&'a dyn Fn(i32, u32) -> String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub message: String,
+    // The title of the item the diagnostic is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn warning(item: Option<&str>, message: String) -> Diagnostic {
+        Diagnostic {
+            level: DiagnosticLevel::Warning,
+            message,
+            item: item.map(str::to_owned),
+        }
+    }
+
+    pub fn error(item: Option<&str>, message: String) -> Diagnostic {
+        Diagnostic {
+            level: DiagnosticLevel::Error,
+            message,
+            item: item.map(str::to_owned),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ItemData {
+    pub title: String,
+    // Days, which may include a fraction of a day, with the end moved off any weekend
+    pub duration: Option<f64>,
+    // An alternative to the duration, in working hours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hours: Option<f64>,
+    // Overrides the chart duration unit for this item
+    #[serde(rename = "durationUnit", skip_serializing_if = "Option::is_none")]
+    pub duration_unit: Option<DurationUnit>,
+    #[serde(rename = "startDate", skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+    // An alternative to the duration, only allowed with a start date
+    #[serde(rename = "endDate", skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<NaiveDate>,
+    // Working days between the end of the previous item and the start of this one, negative to overlap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag: Option<i64>,
+    // An index into the resources, or the name of one
+    pub resource: Option<ResourceRef>,
+    pub open: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    // A short callout shown after the bar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // Where the row is displayed, which does not affect the scheduling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    // From 0 to 100, taken as zero if not given
+    #[serde(
+        rename = "percentComplete",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub percent_complete: Option<f32>,
+    // The originally planned start and duration, to compare against
+    #[serde(
+        rename = "baselineStart",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub baseline_start: Option<NaiveDate>,
+    #[serde(
+        rename = "baselineDuration",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub baseline_duration: Option<f64>,
+    // Separate periods of work shown on the one row, instead of a duration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<SegmentData>>,
+    // More classes for the bar or milestone, to style with --extra-style
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+    // Whether to draw a bar or a milestone, taken from whether there is a duration if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ItemKind>,
+    // A label for items that belong together, which are expected to share a resource
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    // Shown by a marker at the end of the bar, with a glyph for done, blocked and at-risk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SegmentData {
+    // Follows on from the previous segment if not given
+    #[serde(rename = "startDate", skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+    // In the item duration unit
+    pub duration: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Task,
+    Milestone,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationUnit {
+    #[default]
+    Days,
+    // Seven days, so that a week of work spans its weekend
+    Weeks,
+}
+
+impl DurationUnit {
+    pub fn days(&self) -> f64 {
+        match self {
+            DurationUnit::Days => 1.0,
+            DurationUnit::Weeks => 7.0,
+        }
+    }
+}
+
+impl ItemData {
+    /// The duration in days, either given directly, in hours, from the start to the end date, or of all the segments
+    pub fn days(&self, chart_data: &ChartData) -> Option<f64> {
+        let default_unit = chart_data.duration_unit;
+
+        if let Some(hours) = self.hours {
+            return Some(hours / chart_data.hours_per_day());
+        }
+
+        if let Some(ref segments) = self.segments {
+            return Some(
+                segments.iter().map(|segment| segment.duration).sum::<f64>()
+                    * self.duration_unit.unwrap_or(default_unit).days(),
+            );
+        }
+
+        // Durations are in calendar days, so the item ends on its end date
+        match (self.start_date, self.end_date) {
+            (Some(start_date), Some(end_date)) if self.duration.is_none() => {
+                Some((end_date - start_date).num_days() as f64)
+            }
+            _ => self
+                .duration
+                .map(|duration| duration * self.duration_unit.unwrap_or(default_unit).days()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ChartData {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtitle: Option<String>,
+    #[serde(rename = "markedDate")]
+    pub marked_date: Option<MarkedDateData>,
+    #[serde(rename = "chartStart", skip_serializing_if = "Option::is_none")]
+    pub chart_start: Option<NaiveDate>,
+    #[serde(rename = "chartEnd", skip_serializing_if = "Option::is_none")]
+    pub chart_end: Option<NaiveDate>,
+    // The unit of the item durations, days if not given
+    #[serde(rename = "durationUnit", default)]
+    pub duration_unit: DurationUnit,
+    // The length of a working day for items given in hours, 8 if not given
+    #[serde(
+        rename = "hoursPerDay",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hours_per_day: Option<f64>,
+    pub resources: Vec<ResourceData>,
+    pub items: Vec<ItemData>,
+}
+
+// Items refer to a resource by its place in the list, or by name so that the list can be reordered
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ResourceRef {
+    Index(usize),
+    Name(String),
+}
+
+// A resource is just a name, or an object when it has a picture for the legend
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ResourceData {
+    Name(String),
+    Detail {
+        name: String,
+        // A URL or data URI of an image
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        icon: Option<String>,
+        // Shown in a bubble when there is no icon
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initials: Option<String>,
+    },
+}
+
+impl ResourceData {
+    pub fn name(&self) -> &str {
+        match self {
+            ResourceData::Name(name) | ResourceData::Detail { name, .. } => name,
+        }
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        match self {
+            ResourceData::Name(_) => None,
+            ResourceData::Detail { icon, .. } => icon.as_deref(),
+        }
+    }
+
+    pub fn initials(&self) -> Option<&str> {
+        match self {
+            ResourceData::Name(_) => None,
+            ResourceData::Detail { initials, .. } => initials.as_deref(),
+        }
+    }
+}
+
+// A marked date is just a date, or an object when the marker has a label
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MarkedDateData {
+    Date(NaiveDate),
+    Detail {
+        date: NaiveDate,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+}
+
+impl MarkedDateData {
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            MarkedDateData::Date(date) | MarkedDateData::Detail { date, .. } => *date,
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            MarkedDateData::Date(_) => None,
+            MarkedDateData::Detail { label, .. } => label.as_deref(),
+        }
+    }
+}
+
+impl ChartData {
+    pub fn hours_per_day(&self) -> f64 {
+        self.hours_per_day.unwrap_or(8.0)
+    }
+
+    /// The index of the item resource, if it has one and any name is found
+    pub fn resource_index(&self, item: &ItemData) -> Option<usize> {
+        match item.resource {
+            Some(ResourceRef::Index(index)) => Some(index),
+            Some(ResourceRef::Name(ref name)) => self
+                .resources
+                .iter()
+                .position(|resource| resource.name() == name),
+            None => None,
+        }
+    }
+
+    /// Check everything that can be checked up front, reporting all the problems at once
+    pub fn errors(&self) -> Vec<Diagnostic> {
+        let mut errors: Vec<Diagnostic> = vec![];
+
+        // Fail if only one task
+        if self.items.len() < 2 {
+            errors.push(Diagnostic::error(
+                None,
+                "You must provide more than one task".to_string(),
+            ));
+        }
+
+        if let Some(item) = self.items.first() {
+            if item.start_date.is_none() {
+                errors.push(Diagnostic::error(
+                    Some(&item.title),
+                    "First item must contain a start date".to_string(),
+                ));
+            }
+
+            if item.resource.is_none() {
+                errors.push(Diagnostic::error(
+                    Some(&item.title),
+                    "First item must contain a resource index".to_string(),
+                ));
+            }
+        }
+
+        if let Some(hours_per_day) = self.hours_per_day {
+            if !hours_per_day.is_finite() || hours_per_day <= 0.0 || hours_per_day > 24.0 {
+                errors.push(Diagnostic::error(
+                    None,
+                    "Hours per day must be more than 0 and at most 24".to_string(),
+                ));
+            }
+        }
+
+        for item in self.items.iter() {
+            match item.resource {
+                Some(ResourceRef::Index(item_resource_index))
+                    if item_resource_index >= self.resources.len() =>
+                {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' resource index {} is out of range, it must be in 0..{}",
+                            item.title,
+                            item_resource_index,
+                            self.resources.len()
+                        ),
+                    ));
+                }
+                Some(ResourceRef::Name(ref name)) if self.resource_index(item).is_none() => {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' resource '{}' is not one of the resources",
+                            item.title, name
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+
+            if let Some(item_end_date) = item.end_date {
+                if item.duration.is_some() {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' cannot have both an end date and a duration",
+                            item.title
+                        ),
+                    ));
+                }
+
+                match item.start_date {
+                    Some(item_start_date) if item_end_date < item_start_date => {
+                        errors.push(Diagnostic::error(
+                            Some(&item.title),
+                            format!("Item '{}' ends before it starts", item.title),
+                        ));
+                    }
+                    Some(_) => (),
+                    None => {
+                        errors.push(Diagnostic::error(
+                            Some(&item.title),
+                            format!(
+                                "Item '{}' must have a start date to have an end date",
+                                item.title
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            if item.lag.is_some() && item.start_date.is_some() {
+                errors.push(Diagnostic::error(
+                    Some(&item.title),
+                    format!(
+                        "Item '{}' cannot have both a start date and a lag",
+                        item.title
+                    ),
+                ));
+            }
+
+            if let Some(ref segments) = item.segments {
+                if item.duration.is_some() || item.end_date.is_some() {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' cannot have segments as well as a duration or end date",
+                            item.title
+                        ),
+                    ));
+                }
+
+                match segments.first() {
+                    None => errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' must have at least one segment", item.title),
+                    )),
+                    Some(segment) if segment.start_date.is_some() => {
+                        errors.push(Diagnostic::error(
+                            Some(&item.title),
+                            format!(
+                                "Item '{}' first segment starts with the item, so cannot have a start date",
+                                item.title
+                            ),
+                        ))
+                    }
+                    Some(_) => (),
+                }
+
+                if segments
+                    .iter()
+                    .any(|segment| !segment.duration.is_finite() || segment.duration < 0.0)
+                {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' has an invalid segment duration", item.title),
+                    ));
+                }
+            }
+
+            match (item.baseline_start, item.baseline_duration) {
+                (Some(_), Some(baseline_days))
+                    if !baseline_days.is_finite() || baseline_days < 0.0 =>
+                {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' has an invalid baseline duration", item.title),
+                    ));
+                }
+                (Some(_), None) | (None, Some(_)) => {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' must have both a baseline start and duration, or neither",
+                            item.title
+                        ),
+                    ));
+                }
+                _ => (),
+            }
+
+            if let Some(percent_complete) = item.percent_complete {
+                if !(0.0..=100.0).contains(&percent_complete) {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' percent complete must be from 0 to 100",
+                            item.title
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(hours) = item.hours {
+                if item.duration.is_some() || item.end_date.is_some() || item.segments.is_some() {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' cannot have hours as well as a duration, end date or segments",
+                            item.title
+                        ),
+                    ));
+                } else if !hours.is_finite() || hours < 0.0 {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' has an invalid number of hours", item.title),
+                    ));
+                }
+            }
+
+            if item
+                .classes
+                .iter()
+                .any(|class| class.is_empty() || class.contains(char::is_whitespace))
+            {
+                errors.push(Diagnostic::error(
+                    Some(&item.title),
+                    format!("Item '{}' classes must be single words", item.title),
+                ));
+            }
+
+            // Anything else would leave the style block broken
+            if let Some(ref color) = item.color {
+                if colors::parse(color).is_none() {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!(
+                            "Item '{}' has an invalid color '{}', expected #rgb, #rrggbb or a CSS color name",
+                            item.title, color
+                        ),
+                    ));
+                }
+            }
+
+            if item
+                .status
+                .as_ref()
+                .is_some_and(|status| status.is_empty() || status.contains(char::is_whitespace))
+            {
+                errors.push(Diagnostic::error(
+                    Some(&item.title),
+                    format!("Item '{}' status must be a single word", item.title),
+                ));
+            }
+
+            if let Some(item_days) = item.duration {
+                if !item_days.is_finite() {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' has an invalid duration", item.title),
+                    ));
+                } else if item_days < 0.0 {
+                    errors.push(Diagnostic::error(
+                        Some(&item.title),
+                        format!("Item '{}' has a negative duration", item.title),
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+
+    fn error_message(errors: &[Diagnostic]) -> String {
+        errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Assembles a `ChartData` in code, checking it the same way as a data file
+#[derive(Debug, Default)]
+pub struct ChartDataBuilder {
+    title: String,
+    subtitle: Option<String>,
+    marked_date: Option<MarkedDateData>,
+    chart_start: Option<NaiveDate>,
+    chart_end: Option<NaiveDate>,
+    duration_unit: DurationUnit,
+    hours_per_day: Option<f64>,
+    resources: Vec<ResourceData>,
+    items: Vec<ItemData>,
+}
+
+impl ChartDataBuilder {
+    pub fn new() -> ChartDataBuilder {
+        Default::default()
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn subtitle(mut self, subtitle: &str) -> Self {
+        self.subtitle = Some(subtitle.to_string());
+        self
+    }
+
+    pub fn marked_date(mut self, date: NaiveDate) -> Self {
+        self.marked_date = Some(MarkedDateData::Date(date));
+        self
+    }
+
+    pub fn marked_date_with_label(mut self, date: NaiveDate, label: &str) -> Self {
+        self.marked_date = Some(MarkedDateData::Detail {
+            date,
+            label: Some(label.to_string()),
+        });
+        self
+    }
+
+    /// Start the chart on this date rather than at the first item
+    pub fn chart_start(mut self, date: NaiveDate) -> Self {
+        self.chart_start = Some(date);
+        self
+    }
+
+    /// End the chart on this date rather than at the last item
+    pub fn chart_end(mut self, date: NaiveDate) -> Self {
+        self.chart_end = Some(date);
+        self
+    }
+
+    pub fn duration_unit(mut self, unit: DurationUnit) -> Self {
+        self.duration_unit = unit;
+        self
+    }
+
+    pub fn hours_per_day(mut self, hours: f64) -> Self {
+        self.hours_per_day = Some(hours);
+        self
+    }
+
+    pub fn add_resource(mut self, name: &str) -> Self {
+        self.resources.push(ResourceData::Name(name.to_string()));
+        self
+    }
+
+    pub fn add_resource_with_icon(mut self, name: &str, icon: &str) -> Self {
+        self.resources.push(ResourceData::Detail {
+            name: name.to_string(),
+            icon: Some(icon.to_string()),
+            initials: None,
+        });
+        self
+    }
+
+    /// A task without a start date follows the previous item, and without a resource uses its resource
+    pub fn add_task(
+        mut self,
+        title: &str,
+        duration: f64,
+        start_date: Option<NaiveDate>,
+        resource_index: Option<usize>,
+    ) -> Self {
+        self.items.push(ItemData {
+            title: title.to_string(),
+            duration: Some(duration),
+            start_date,
+            resource: resource_index.map(ResourceRef::Index),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn add_milestone(
+        mut self,
+        title: &str,
+        start_date: Option<NaiveDate>,
+        resource_index: Option<usize>,
+    ) -> Self {
+        self.items.push(ItemData {
+            title: title.to_string(),
+            start_date,
+            resource: resource_index.map(ResourceRef::Index),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn build(self) -> Result<ChartData, Box<dyn Error>> {
+        let chart_data = ChartData {
+            title: self.title,
+            subtitle: self.subtitle,
+            marked_date: self.marked_date,
+            chart_start: self.chart_start,
+            chart_end: self.chart_end,
+            duration_unit: self.duration_unit,
+            hours_per_day: self.hours_per_day,
+            resources: self.resources,
+            items: self.items,
+        };
+        let errors = chart_data.errors();
+
+        if !errors.is_empty() {
+            return Err(From::from(ChartData::error_message(&errors)));
+        }
+
+        Ok(chart_data)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduleItemData {
+    pub title: String,
+    #[serde(rename = "resource")]
+    pub resource_index: usize,
+    #[serde(rename = "startDate")]
+    pub start_date: NaiveDate,
+    #[serde(rename = "endDate")]
+    pub end_date: NaiveDate,
+    // Not present if the item has no duration
+    pub duration: Option<f64>,
+    pub milestone: bool,
+    pub open: bool,
+    // Days the item can slip without delaying the next item or the project end
+    #[serde(rename = "slackDays")]
+    pub slack_days: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduleResourceColor {
+    pub name: String,
+    // As used in CSS, e.g. #1f77b4
+    pub color: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduleData {
+    pub title: String,
+    #[serde(rename = "startDate")]
+    pub start_date: NaiveDate,
+    #[serde(rename = "endDate")]
+    pub end_date: NaiveDate,
+    pub resources: Vec<String>,
+    // The name and RGB color of each resource, for building a legend elsewhere
+    #[serde(rename = "resourceColors")]
+    pub resource_colors: Vec<ScheduleResourceColor>,
+    pub items: Vec<ScheduleItemData>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Gutter {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+impl Gutter {
+    pub fn height(&self) -> f32 {
+        self.bottom + self.top
+    }
+
+    pub fn width(&self) -> f32 {
+        self.right + self.left
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RenderData {
+    title: String,
+    subtitle: Option<String>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    gutter: Gutter,
+    row_gutter: Gutter,
+    row_height: f32,
+    resource_gutter: Gutter,
+    resource_height: f32,
+    marked_date: Option<NaiveDate>,
+    marked_date_label: Option<String>,
+    // From the start of the first item to the end of the last one
+    calendar_days: i64,
+    project_start_date: NaiveDate,
+    project_end_date: NaiveDate,
+    project_offsets: (f32, f32),
+    working_days: usize,
+    marked_date_offset: Option<f32>,
+    // The strftime format for showing dates
+    date_format: String,
+    // The fraction of all the task days that are complete
+    completion: f32,
+    // The chart data as JSON, if it is to be embedded
+    #[serde(skip)]
+    source: Option<String>,
+    // The offset and width of every weekend day
+    weekend_offsets: Vec<(f32, f32)>,
+    // The offset and week number of the first day of every week
+    week_offsets: Vec<(f32, u32)>,
+    // The band for the phases goes between the title and the column headings
+    phase_height: f32,
+    // All the rows share the one band, for a compact timeline of the milestones
+    single_band: bool,
+    phases: Vec<PhaseRenderData>,
+    // Only the days with some tasks on them
+    utilization: Vec<UtilizationRenderData>,
+    title_width: f32,
+    rect_corner_radius: f32,
+    styles: Vec<String>,
+    cols: Vec<ColumnRenderData>,
+    rows: Vec<RowRenderData>,
+    resources: Vec<ResourceRenderData>,
+}
+
+impl RenderData {
+    fn format_date(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    fn resource_colors(&self) -> Vec<(String, u32)> {
+        self.resources
+            .iter()
+            .map(|resource| (resource.name.clone(), resource.color))
+            .collect()
+    }
+
+    fn has_title_column(&self) -> bool {
+        self.title_width > 0.0
+    }
+
+    // Stable, so that rows which compare equal keep their authored order
+    fn sort_rows(&mut self, order: RowOrder) {
+        match order {
+            // Items without an order value count as zero, and ties keep the input file order
+            RowOrder::None => self.rows.sort_by_key(|row| row.order.unwrap_or(0)),
+            RowOrder::Start => self.rows.sort_by_key(|row| row.start_date),
+            RowOrder::Resource => self.rows.sort_by_key(|row| row.resource_index),
+        }
+    }
+
+    // Round the positions to whole pixels, rounding both ends of anything with a width,
+    // except for the thin lines, which go on the half pixel so that they fill one pixel exactly
+    fn snap_to_pixels(&mut self) {
+        fn snap_span(offset: f32, length: f32) -> (f32, f32) {
+            (offset.round(), (offset + length).round() - offset.round())
+        }
+
+        // Everything is placed from the gutters, so whole gutters keep it all on whole pixels
+        for gutter in [&mut self.gutter, &mut self.resource_gutter] {
+            gutter.left = gutter.left.round();
+            gutter.top = gutter.top.round();
+            gutter.right = gutter.right.round();
+            gutter.bottom = gutter.bottom.round();
+        }
+
+        self.title_width = self.title_width.round();
+
+        let mut x = self.gutter.left + self.title_width;
+
+        for col in self.cols.iter_mut() {
+            col.width = snap_span(x, col.width).1;
+            x += col.width;
+        }
+
+        for row in self.rows.iter_mut() {
+            if let Some(length) = row.length {
+                row.length = Some(snap_span(row.offset, length).1);
+            }
+
+            for segment in row.segments.iter_mut() {
+                *segment = snap_span(segment.0, segment.1);
+            }
+
+            row.baseline = row.baseline.map(|(baseline_offset, baseline_length)| {
+                snap_span(baseline_offset, baseline_length)
+            });
+
+            row.offset = row.offset.round();
+            row.height = row.height.round();
+        }
+
+        for weekend in self.weekend_offsets.iter_mut() {
+            *weekend = snap_span(weekend.0, weekend.1);
+        }
+
+        for week in self.week_offsets.iter_mut() {
+            week.0 = week.0.floor() + 0.5;
+        }
+
+        self.marked_date_offset = self.marked_date_offset.map(f32::round);
+
+        // The rows are stacked down from the top gutter, so whole heights keep them all on whole pixels
+        self.row_height = self.row_height.round();
+        self.resource_height = self.resource_height.round();
+        self.phase_height = self.phase_height.round();
+    }
+}
+
+#[derive(Debug)]
+struct LayoutOptions {
+    title_width: f32,
+    max_month_width: f32,
+    px_per_day: Option<f32>,
+    wrap_titles: bool,
+    milestones_only: bool,
+    phase_bands: bool,
+    utilization: bool,
+    auto_status: bool,
+    today: Option<NaiveDate>,
+    max_width: Option<f32>,
+    corner_radius: f32,
+    // Around the outside of the chart, with the title inside the top margin
+    margins: Gutter,
+    min_bar_width: f32,
+    compress_weekends: bool,
+    calendar_days: bool,
+    uniform_months: bool,
+    lead_days: u32,
+    autofill_durations: bool,
+    week_start: WeekStart,
+    date_format: String,
+    color_saturation: f32,
+    color_value: f32,
+    hue_start: Option<f32>,
+    seed: Option<u64>,
+    extra_styles: Vec<String>,
+    window: Option<(NaiveDate, NaiveDate)>,
+}
+
+#[derive(Debug)]
+struct RenderOptions {
+    legend: LegendPosition,
+    legend_stats: bool,
+    rtl: bool,
+    zebra: bool,
+    swimlanes: bool,
+    weekend_bands: bool,
+    weekend_marks: bool,
+    week_lines: bool,
+    week_numbers: bool,
+    critical_path: bool,
+    task_guides: bool,
+    summary: bool,
+    baseline: bool,
+    arrows: bool,
+    show_duration: bool,
+    show_slack: bool,
+    title_divider: bool,
+    task_header: bool,
+    show_bounds: bool,
+    title_align: Option<TitleAlign>,
+    bar_style: BarStyle,
+    milestone_shape: MilestoneShape,
+    milestone_size: f32,
+    scale: f32,
+    page: Option<PageSize>,
+    max_height: Option<f32>,
+    scroll_to: usize,
+    max_title_chars: Option<usize>,
+    landscape: bool,
+    page_center: bool,
+    fragment: bool,
+    precision: usize,
+    class_prefix: String,
+}
+
+impl RenderOptions {
+    fn class(&self, name: &str) -> String {
+        format!("{}{}", self.class_prefix, name)
+    }
+
+    // Written with the fixed number of decimal places, so the same chart is the same on every platform
+    fn round(&self, value: f32) -> f32 {
+        let factor = 10f64.powi(self.precision as i32);
+
+        // Adding zero turns a negative zero into a positive one
+        ((value as f64 * factor).round() / factor) as f32 + 0.0
+    }
+
+    fn round_point(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.round(x), self.round(y))
+    }
+
+    fn classes(&self, names: &[&str]) -> String {
+        names
+            .iter()
+            .map(|name| self.class(name))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Prefix every class selector in the style rules, leaving the declarations alone
+    fn style(&self, style: &str) -> String {
+        let mut prefixed = String::with_capacity(style.len());
+        let mut in_declarations = false;
+        let mut chars = style.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            prefixed.push(c);
+
+            match c {
+                '{' => in_declarations = true,
+                '}' => in_declarations = false,
+                '.' if !in_declarations
+                    && chars
+                        .peek()
+                        .is_some_and(|c| c.is_alphabetic() || *c == '_' || *c == '-') =>
+                {
+                    prefixed.push_str(&self.class_prefix)
+                }
+                _ => (),
+            }
+        }
+
+        prefixed
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RowRenderData {
+    // Taller than the chart row height when the title is wrapped
+    height: f32,
+    // Added to the bar or milestone classes
+    classes: Vec<String>,
+    status: Option<String>,
+    // The title split to fit the title column, or just the title if not wrapped
+    title_lines: Vec<String>,
+    // Where the item is in the chart data, as the rows may be sorted
+    item_index: usize,
+    // The index of the item this one follows on from
+    predecessor: Option<usize>,
+    note: Option<String>,
+    // The offset and length of the originally planned bar
+    baseline: Option<(f32, f32)>,
+    // The offset and length of each separate period of work, if the bar is split
+    segments: Vec<(f32, f32)>,
+    order: Option<i64>,
+    title: String,
+    resource_index: usize,
+    // Prefix of the bar style classes, either the resource or the item colors
+    bar_class: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    // Working-day adjusted duration, not present for milestones
+    duration: Option<f64>,
+    offset: f32,
+    // Not present for milestones
+    length: Option<f32>,
+    milestone: bool,
+    open: bool,
+    // On the chain of items with no slack before the project end
+    critical: bool,
+    // How far the item can slip without delaying the item chained to it, or the project end
+    slack: f32,
+    slack_days: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct ResourceRenderData {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initials: Option<String>,
+    num_tasks: usize,
+    // Total days of the tasks, as given in the chart data
+    num_days: f64,
+    color: u32,
+    // For the top of a gradient bar
+    light_color: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct UtilizationRenderData {
+    offset: f32,
+    width: f32,
+    // The number of tasks on the day for each resource
+    counts: Vec<usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct PhaseRenderData {
+    name: String,
+    offset: f32,
+    length: f32,
+}
+
+#[derive(Serialize, Debug)]
+struct ColumnRenderData {
+    width: f32,
+    month_name: String,
+}
+
+impl<'a> GanttChartTool<'a> {
+    pub fn new(log: &'a dyn GanttChartLog) -> GanttChartTool {
+        GanttChartTool {
+            log,
+            quiet: false,
+            diagnostics: RefCell::new(vec![]),
+            month_name: None,
+        }
+    }
+
+    /// Make each month heading from its year and month (1 to 12), e.g. `|year, month| format!("{month}/{year}")`
+    pub fn with_month_name(mut self, month_name: &'a dyn Fn(i32, u32) -> String) -> Self {
+        self.month_name = Some(month_name);
+        self
+    }
+
+    fn num_warnings(&self) -> usize {
+        self.diagnostics
+            .borrow()
+            .iter()
+            .filter(|diagnostic| diagnostic.level == DiagnosticLevel::Warning)
+            .count()
+    }
+
+    // Warnings go through here so that they can be suppressed and collected
+    fn warning(&self, item: Option<&str>, args: Arguments) {
+        self.diagnostics
+            .borrow_mut()
+            .push(Diagnostic::warning(item, args.to_string()));
+
+        if !self.quiet {
+            self.log.warning(args);
+        }
+    }
+
+    pub fn run(
+        &mut self,
+        args: impl IntoIterator<Item = std::ffi::OsString>,
+    ) -> Result<(), Box<dyn Error>> {
+        let cli = match Cli::try_parse_from(args) {
+            Ok(cli) => cli,
+            Err(err) => {
+                output!(self.log, "{}", err.to_string());
+                return Ok(());
+            }
+        };
+
+        self.quiet = cli.quiet;
+
+        let result = self.run_cli(&cli);
+
+        if let Some(ref path) = cli.diagnostics_json {
+            if let Err(ref err) = result {
+                let mut diagnostics = self.diagnostics.borrow_mut();
+
+                // Validation errors have already been collected individually
+                if !diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+                {
+                    diagnostics.push(Diagnostic::error(None, err.to_string()));
+                }
+            }
+
+            self.write_diagnostics(path)?;
+        }
+
+        result
+    }
+
+    /// Run the tool as for `run`, but return the chart instead of writing it to the output file
+    ///
+    /// The string is empty when the arguments ask for something other than a chart, such as `--verify`.
+    pub fn run_to_string(
+        &mut self,
+        args: impl IntoIterator<Item = std::ffi::OsString>,
+    ) -> Result<String, Box<dyn Error>> {
+        let cli = Cli::try_parse_from(args)?;
+
+        self.quiet = cli.quiet;
+
+        Ok(self
+            .build_output(&cli, cli.input_file.as_deref())?
+            .unwrap_or_default())
+    }
+
+    fn run_cli(&self, cli: &Cli) -> Result<(), Box<dyn Error>> {
+        if let Some(ref output_dir) = cli.output_dir {
+            return self.run_batch(cli, output_dir);
+        }
+
+        let emits = cli.emit_variants()?;
+
+        if let Some(output) = self.build_output(cli, cli.input_file.as_deref())? {
+            match cli.output_file {
+                Some(ref output_file) if !emits.is_empty() => {
+                    Self::write_emitted_files(&emits, output_file, &output)?
+                }
+                _ => Self::write_output_file(cli.get_output()?, &output)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // The one chart is rasterized again for each PNG scale
+    fn write_emitted_files(
+        emits: &[Emit],
+        output_file: &std::path::Path,
+        output: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        for emit in emits.iter() {
+            let path = emit.path(output_file);
+            let bytes = match emit {
+                Emit::Svg => output.as_bytes().to_vec(),
+                Emit::Png(scale) => Self::rasterize(output, *scale)?,
+            };
+
+            File::create(&path)
+                .context(format!(
+                    "Unable to create file '{}'",
+                    path.to_string_lossy()
+                ))?
+                .write_all(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "png")]
+    fn rasterize(svg: &str, scale: f32) -> Result<Vec<u8>, Box<dyn Error>> {
+        use resvg::{
+            tiny_skia::{
+                Pixmap,
+                Transform,
+            },
+            usvg::{
+                Options,
+                Tree,
+            },
+        };
+
+        let mut options = Options::default();
+        let fontdb = options.fontdb_mut();
+
+        fontdb.load_system_fonts();
+
+        // Text in a missing font falls back to the serif family, so make that a sans font if there is one
+        let sans_family = fontdb
+            .faces()
+            .flat_map(|face| face.families.iter().map(|(family, _)| family))
+            .find(|family| family.contains("Sans"))
+            .cloned();
+
+        if let Some(family) = sans_family {
+            fontdb.set_serif_family(family);
+        }
+
+        let tree = Tree::from_str(svg, &options)?;
+        let size = tree.size().to_int_size();
+        let (width, height) = (
+            (size.width() as f32 * scale).ceil() as u32,
+            (size.height() as f32 * scale).ceil() as u32,
+        );
+        let Some(mut pixmap) = Pixmap::new(width, height) else {
+            bail!("Unable to make a {}x{} image", width, height);
+        };
+
+        resvg::render(
+            &tree,
+            Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        Ok(pixmap.encode_png()?)
+    }
+
+    #[cfg(not(feature = "png"))]
+    fn rasterize(_svg: &str, _scale: f32) -> Result<Vec<u8>, Box<dyn Error>> {
+        bail!("PNG output needs the tool to be built with the 'png' feature");
+    }
+
+    // A file that fails is reported and skipped, unless stopping at the first failure
+    fn run_batch(&self, cli: &Cli, output_dir: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let inputs = cli.get_batch_inputs()?;
+        let extension = match cli.output_format() {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Json => "json",
+            OutputFormat::Html => "html",
+        };
+        let mut output_paths: Vec<PathBuf> = Vec::with_capacity(inputs.len());
+
+        // Inputs with the same name, such as plan.json5 and plan.toml, would overwrite each other
+        for (i, input) in inputs.iter().enumerate() {
+            let mut path = output_dir.join(input.file_stem().unwrap_or_default());
+
+            path.set_extension(extension);
+
+            if let Some(other) = output_paths.iter().position(|other| *other == path) {
+                bail!(
+                    "Input files '{}' and '{}' would both be written to '{}'",
+                    inputs[other].to_string_lossy(),
+                    inputs[i].to_string_lossy(),
+                    path.to_string_lossy()
+                );
+            }
+
+            output_paths.push(path);
+        }
+
+        let mut num_failed = 0;
+
+        for (input, path) in inputs.iter().zip(output_paths.iter()) {
+            let result = self
+                .build_output(cli, Some(input))
+                .and_then(|output| match output {
+                    Some(output) => {
+                        let file = File::create(path).context(format!(
+                            "Unable to create file '{}'",
+                            path.to_string_lossy()
+                        ))?;
+
+                        Self::write_output_file(Box::new(file), &output)
+                    }
+                    None => Ok(()),
+                });
+
+            if let Err(err) = result {
+                if cli.fail_fast {
+                    return Err(err);
+                }
+
+                error!(self.log, "{}: {}", input.to_string_lossy(), err);
+                num_failed += 1;
+            }
+        }
+
+        if num_failed > 0 {
+            bail!(
+                "{} of {} files could not be rendered",
+                num_failed,
+                inputs.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    // Everything up to the output file, which is not produced when only checking or dumping the layout
+    fn build_output(
+        &self,
+        cli: &Cli,
+        input_file: Option<&std::path::Path>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        // Only the warnings for this file count, when there are several
+        let num_warnings = self.num_warnings();
+        let chart_data = Self::read_chart_file(
+            Cli::get_input(input_file)?,
+            Cli::get_input_format(input_file),
+        )?;
+
+        let mut render_data = self.process_chart_data(&cli.layout_options()?, &chart_data)?;
+
+        if !cli.allow_mixed_groups {
+            self.check_groups(&chart_data, &render_data);
+        }
+
+        if cli.strict && self.num_warnings() > num_warnings {
+            bail!(
+                "{} warning(s) treated as errors with --strict",
+                self.num_warnings() - num_warnings
+            );
+        }
+
+        let render_options = cli.render_options()?;
+
+        if cli.verify {
+            output!(self.log, "OK");
+            return Ok(None);
+        }
+
+        render_data.sort_rows(cli.sort);
+
+        if cli.snap_pixels {
+            render_data.snap_to_pixels();
+        }
+
+        if cli.embed_source {
+            render_data.source = Some(serde_json::to_string(&chart_data)?);
+        }
+
+        if let Some(format) = cli.dump_layout {
+            match format {
+                LayoutFormat::Debug => debug!(self.log, "{:#?}", render_data),
+                LayoutFormat::Json => {
+                    debug!(self.log, "{}", serde_json::to_string_pretty(&render_data)?)
+                }
+            }
+
+            return Ok(None);
+        }
+
+        // Without the class prefix, as --extra-style adds it, so that changed rules can go straight back in
+        if cli.dump_styles {
+            for style in render_data.styles.iter() {
+                output!(self.log, "{}", style);
+            }
+
+            return Ok(None);
+        }
+
+        let output = match cli.output_format() {
+            OutputFormat::Svg => self.render_chart(&render_options, &render_data)?,
+            OutputFormat::Json => {
+                serde_json::to_string_pretty(&Self::build_schedule(&render_data))?
+            }
+            OutputFormat::Html => Self::build_html(
+                &render_data.title,
+                &self.render_chart(&render_options, &render_data)?,
+            ),
+        };
+
+        Ok(Some(output))
+    }
+
+    // A page that shows the chart as big as fits the window, to open straight in a browser
+    fn build_html(title: &str, svg: &str) -> String {
+        let title = title
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ margin: 0; padding: 1em; }}
+svg {{ display: block; max-width: 100%; height: auto; }}
+</style>
+</head>
+<body>
+{svg}
+</body>
+</html>
+"#
+        )
+    }
+
+    // A path of '-' writes the diagnostics to stderr
+    fn write_diagnostics(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(&*self.diagnostics.borrow())?;
+
+        if path.as_os_str() == "-" {
+            writeln!(io::stderr(), "{}", json)?;
+        } else {
+            File::create(path)
+                .context(format!(
+                    "Unable to create file '{}'",
+                    path.to_string_lossy()
+                ))?
+                .write_all(json.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_chart_file(
+        mut reader: Box<dyn Read>,
+        format: InputFormat,
+    ) -> Result<ChartData, Box<dyn Error>> {
+        let mut content = String::new();
+
+        reader.read_to_string(&mut content)?;
+
+        let chart_data: ChartData = match format {
+            InputFormat::Json5 => json5::from_str(&content)?,
+            InputFormat::Toml => toml::from_str(&content)?,
+            InputFormat::Csv => Self::read_csv_chart(&content)?,
+        };
+
+        Ok(chart_data)
+    }
+
+    // Columns are found by name if there is a header row, otherwise they are in the default order
+    fn read_csv_chart(content: &str) -> Result<ChartData, Box<dyn Error>> {
+        static COLUMNS: [&str; 6] = [
+            "title",
+            "start",
+            "duration",
+            "resource",
+            "milestone",
+            "open",
+        ];
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(content.as_bytes());
+        let mut records = reader.records().collect::<Result<Vec<_>, _>>()?;
+        let has_header = records.first().is_some_and(|record| {
+            record
+                .iter()
+                .any(|field| field.eq_ignore_ascii_case(COLUMNS[0]))
+        });
+        let columns: Vec<Option<usize>> = if has_header {
+            let header = records.remove(0);
+
+            COLUMNS
+                .iter()
+                .map(|name| {
+                    header
+                        .iter()
+                        .position(|field| field.eq_ignore_ascii_case(name))
+                })
+                .collect()
+        } else {
+            (0..COLUMNS.len()).map(Some).collect()
+        };
+        let field = |record: &csv::StringRecord, column: usize| -> Option<String> {
+            columns[column]
+                .and_then(|i| record.get(i))
+                .filter(|field| !field.is_empty())
+                .map(str::to_string)
+        };
+        let flag = |record: &csv::StringRecord, column: usize| {
+            field(record, column).is_some_and(|field| {
+                ["true", "yes", "y", "1"]
+                    .iter()
+                    .any(|value| field.eq_ignore_ascii_case(value))
+            })
+        };
+
+        let mut resources: Vec<String> = vec![];
+        let mut items: Vec<ItemData> = vec![];
+
+        for (i, record) in records.iter().enumerate() {
+            let line = i + if has_header { 2 } else { 1 };
+            let start_date = match field(record, 1) {
+                Some(start) => Some(start.parse::<NaiveDate>().context(format!(
+                    "Line {} has an invalid start date '{}'",
+                    line, start
+                ))?),
+                None => None,
+            };
+            let duration = match field(record, 2) {
+                Some(duration) if !flag(record, 4) => Some(duration.parse::<f64>().context(
+                    format!("Line {} has an invalid duration '{}'", line, duration),
+                )?),
+                _ => None,
+            };
+            // Resources are numbered in the order they first appear
+            let resource_index = field(record, 3).map(|name| {
+                resources
+                    .iter()
+                    .position(|resource| *resource == name)
+                    .unwrap_or_else(|| {
+                        resources.push(name);
+                        resources.len() - 1
+                    })
+            });
+
+            items.push(ItemData {
+                title: field(record, 0).unwrap_or_default(),
+                duration,
+                start_date,
+                resource: resource_index.map(ResourceRef::Index),
+                open: Some(flag(record, 5)),
+                ..Default::default()
+            });
+        }
+
+        Ok(ChartData {
+            title: "Gantt Chart".to_string(),
+            subtitle: None,
+            marked_date: None,
+            chart_start: None,
+            chart_end: None,
+            duration_unit: DurationUnit::Days,
+            hours_per_day: None,
+            resources: resources.into_iter().map(ResourceData::Name).collect(),
+            items,
+        })
+    }
+
+    fn write_output_file(mut writer: Box<dyn Write>, output: &str) -> Result<(), Box<dyn Error>> {
+        write!(writer, "{}", output)?;
+
+        Ok(())
+    }
+
+    // Other statuses are shown with a dot
+    fn status_glyph(status: &str) -> Option<&'static str> {
+        match status {
+            "done" => Some("✓"),
+            "blocked" => Some("✗"),
+            "at-risk" => Some("⚠"),
+            _ => None,
+        }
+    }
+
+    fn hsv_to_rgb(h: f32, s: f32, v: f32) -> u32 {
+        let h_i = (h * 6.0) as usize;
+        let f = h * 6.0 - h_i as f32;
+        let p = v * (1.0 - s);
+        let q = v * (1.0 - f * s);
+        let t = v * (1.0 - (1.0 - f) * s);
+
+        // Each channel must stay within its byte, or it spills into the next one
+        fn channel(c: f32) -> u32 {
+            (c * 255.0).round().clamp(0.0, 255.0) as u32
+        }
+
+        fn rgb(r: f32, g: f32, b: f32) -> u32 {
+            channel(r) << 16 | channel(g) << 8 | channel(b)
+        }
+
+        if h_i == 0 {
+            rgb(v, t, p)
+        } else if h_i == 1 {
+            rgb(q, v, p)
+        } else if h_i == 2 {
+            rgb(p, v, t)
+        } else if h_i == 3 {
+            rgb(p, q, v)
+        } else if h_i == 4 {
+            rgb(t, p, v)
+        } else {
+            rgb(v, p, q)
+        }
+    }
+
+    // Break between words where possible, and inside any word too long for a line of its own
+    fn wrap_title(title: &str, max_chars: usize) -> Vec<String> {
+        let max_chars = max_chars.max(1);
+        let mut lines: Vec<String> = vec![];
+        let mut line = String::new();
+
+        for word in title.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+
+            if !line.is_empty() && line.chars().count() + 1 + word.len() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            while word.len() > max_chars {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+
+                lines.push(word.drain(..max_chars).collect());
+            }
+
+            if !word.is_empty() {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+
+                line.extend(word);
+            }
+        }
+
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    fn validate_chart_data(&self, chart_data: &ChartData) -> Result<(), Box<dyn Error>> {
+        let errors = chart_data.errors();
+
+        if !errors.is_empty() {
+            let message = ChartData::error_message(&errors);
+
+            self.diagnostics.borrow_mut().extend(errors);
+
+            return Err(From::from(message));
+        }
+
+        Ok(())
+    }
+
+    // Items in a group are usually done by the one resource, so a mix is likely a mistake
+    // Uses the resource each row ends up with, as items without one carry on with the resource before
+    fn check_groups(&self, chart_data: &ChartData, render_data: &RenderData) {
+        let mut groups: Vec<(&str, Vec<&RowRenderData>)> = vec![];
+
+        for row in render_data.rows.iter() {
+            if let Some(ref name) = chart_data.items[row.item_index].group {
+                match groups.iter_mut().find(|(group, _)| group == name) {
+                    Some((_, rows)) => rows.push(row),
+                    None => groups.push((name, vec![row])),
+                }
+            }
+        }
+
+        for (name, rows) in groups {
+            let others: Vec<&str> = rows
+                .iter()
+                .filter(|row| row.resource_index != rows[0].resource_index)
+                .map(|row| row.title.as_str())
+                .collect();
+
+            if !others.is_empty() {
+                self.warning(
+                    Some(&rows[0].title),
+                    format_args!(
+                        "Group '{}' mixes resources, '{}' and '{}' do not use the same resource",
+                        name,
+                        rows[0].title,
+                        others.join("', '")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn process_chart_data(
+        &self,
+        layout: &LayoutOptions,
+        chart_data: &ChartData,
+    ) -> Result<RenderData, Box<dyn Error>> {
+        fn num_days_in_month(year: i32, month: u32) -> u32 {
+            // the first day of the next month...
+            let (y, m) = if month == 12 {
+                (year + 1, 1)
+            } else {
+                (year, month + 1)
+            };
+            let d = NaiveDate::from_ymd_opt(y, m, 1).unwrap(); // FIXME unwrap
+
+            // ...is preceded by the last day of the original month
+            d.pred_opt().unwrap().day() // FIXME unwrap
+        }
+
+        fn is_weekend(date: &NaiveDate) -> bool {
+            matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+        }
+
+        // Move on by a number of days, past the weekend if that is where it ends up and weekends are not worked
+        fn skip_weekend(
+            date: NaiveDate,
+            day_fraction: f64,
+            days: f64,
+            calendar_days: bool,
+        ) -> (NaiveDate, f64, f64) {
+            // Only whole days are moved past a weekend, the fraction carries over
+            let elapsed_days = day_fraction + days;
+            let whole_days = elapsed_days.floor() as i64;
+            // FIXME unwrap
+            let duration = match (date + Duration::try_days(whole_days).unwrap()).weekday() {
+                Weekday::Sat if !calendar_days => Duration::try_days(whole_days + 2).unwrap(),
+                Weekday::Sun if !calendar_days => Duration::try_days(whole_days + 1).unwrap(),
+                _ => Duration::try_days(whole_days).unwrap(),
+            };
+
+            (
+                date + duration,
+                duration.num_days() as f64 + elapsed_days.fract() - day_fraction,
+                elapsed_days.fract(),
+            )
+        }
+
+        fn add_working_days(mut date: NaiveDate, days: i64, calendar_days: bool) -> NaiveDate {
+            let step = Duration::try_days(days.signum()).unwrap(); // FIXME unwrap
+
+            for _ in 0..days.abs() {
+                date += step;
+
+                while !calendar_days && is_weekend(&date) {
+                    date += step;
+                }
+            }
+
+            date
+        }
+
+        self.validate_chart_data(chart_data)?;
+
+        let title_width = layout.title_width;
+        let mut max_month_width = layout.max_month_width;
+
+        let mut start_date = NaiveDate::MAX;
+        let mut end_date = NaiveDate::MIN;
+        let mut date = NaiveDate::MIN;
+        // How far into the current date the previous item finished
+        let mut day_fraction: f64 = 0.0;
+        let mut shadow_durations: Vec<Option<f64>> = Vec::with_capacity(chart_data.items.len());
+        // The days from the item start and the shadow duration of each segment of each item
+        let mut segment_spans: Vec<Vec<(f64, f64)>> = Vec::with_capacity(chart_data.items.len());
+        // Calendar days each item was moved by its lag
+        let mut lag_days: Vec<i64> = Vec::with_capacity(chart_data.items.len());
+        let mut prev_resource_index: Option<usize> = None;
+        let mut resource_start_dates: HashMap<(usize, NaiveDate), &str> = HashMap::new();
+        // Items already warned about for starting before the previous item ends
+        let mut early_starts: HashSet<(usize, usize)> = HashSet::new();
+
+        // Determine the project start & end dates
+        for (i, item) in chart_data.items.iter().enumerate() {
+            let item_resource_index = chart_data.resource_index(item).or(prev_resource_index);
+
+            // A lead can overlap the previous item, but not start before the project
+            if let (None, Some(lag)) = (item.start_date, item.lag) {
+                let lagged_date = add_working_days(date, lag, layout.calendar_days).max(start_date);
+
+                // Starting on another day starts at the beginning of it
+                if lagged_date != date {
+                    day_fraction = 0.0;
+                }
+
+                lag_days.push((lagged_date - date).num_days());
+                date = lagged_date;
+            } else {
+                lag_days.push(0);
+            }
+
+            if let Some(item_start_date) = item.start_date {
+                // Only items following on in the same resource are expected to be sequential
+                if i > 0 && item_start_date < date && item_resource_index == prev_resource_index {
+                    self.warning(
+                        Some(&item.title),
+                        format_args!(
+                            "Item '{}' starts on {} before the previous item '{}' ends on {}",
+                            item.title,
+                            item_start_date,
+                            chart_data.items[i - 1].title,
+                            date
+                        ),
+                    );
+                    early_starts.insert((i - 1, i));
+                }
+
+                if let Some(resource_index) = item_resource_index {
+                    if let Some(other_title) = resource_start_dates
+                        .insert((resource_index, item_start_date), item.title.as_str())
+                    {
+                        self.warning(
+                            Some(&item.title),
+                            format_args!(
+                                "Items '{}' and '{}' have the same start date {}",
+                                other_title, item.title, item_start_date
+                            ),
+                        );
+                    }
+                }
+
+                date = item_start_date;
+                day_fraction = 0.0;
+
+                if item_start_date < start_date {
+                    // Move the start if it falls on a weekend
+                    start_date = match date.weekday() {
+                        Weekday::Sat if !layout.calendar_days => {
+                            date + Duration::try_days(2).unwrap() // FIXME unwrap
+                        }
+                        Weekday::Sun if !layout.calendar_days => {
+                            date + Duration::try_days(1).unwrap() // FIXME unwrap
+                        }
+                        _ => date,
+                    };
+                }
+            }
+
+            // Skip the weekends and update a shadow list of the _real_ durations
+            let mut spans: Vec<(f64, f64)> = vec![];
+
+            if let Some(ref segments) = item.segments {
+                // The item spans all its segments, each placed in days from the item start
+                let unit_days = item
+                    .duration_unit
+                    .unwrap_or(chart_data.duration_unit)
+                    .days();
+                let (mut segment_date, mut segment_fraction) = (date, day_fraction);
+
+                for segment in segments.iter() {
+                    if let Some(segment_start_date) = segment.start_date {
+                        (segment_date, segment_fraction) = (segment_start_date, 0.0);
+                    }
+
+                    let (next_date, shadow_days, next_fraction) = skip_weekend(
+                        segment_date,
+                        segment_fraction,
+                        segment.duration * unit_days,
+                        layout.calendar_days,
+                    );
+
+                    spans.push((
+                        (segment_date - date).num_days() as f64 + segment_fraction - day_fraction,
+                        shadow_days,
+                    ));
+                    (segment_date, segment_fraction) = (next_date, next_fraction);
+                }
+
+                shadow_durations.push(Some(
+                    (segment_date - date).num_days() as f64 + segment_fraction - day_fraction,
+                ));
+                (date, day_fraction) = (segment_date, segment_fraction);
+            } else if let Some(item_days) = item.days(chart_data) {
+                let (next_date, shadow_days, next_fraction) =
+                    skip_weekend(date, day_fraction, item_days, layout.calendar_days);
+
+                shadow_durations.push(Some(shadow_days));
+                (date, day_fraction) = (next_date, next_fraction);
+            } else if let Some(next_start_date) = chart_data.items[i + 1..]
+                .iter()
+                .find_map(|item| item.start_date)
+                .filter(|next_start_date| {
+                    layout.autofill_durations
+                        && item.start_date.is_none()
+                        && item.kind != Some(ItemKind::Milestone)
+                        && *next_start_date > date
+                })
+            {
+                // Fill the gap up to the next item with a start date
+                shadow_durations.push(Some(
+                    (next_start_date - date).num_days() as f64 - day_fraction,
+                ));
+                (date, day_fraction) = (next_start_date, 0.0);
+            } else if item.kind == Some(ItemKind::Task) {
+                // A task is never drawn as a milestone, so without a duration it takes a day
+                let (next_date, shadow_days, next_fraction) =
+                    skip_weekend(date, day_fraction, 1.0, layout.calendar_days);
+
+                shadow_durations.push(Some(shadow_days));
+                (date, day_fraction) = (next_date, next_fraction);
+            } else {
+                shadow_durations.push(None);
+            }
+
+            segment_spans.push(spans);
+
+            if end_date < date {
+                end_date = date;
+            }
+
+            prev_resource_index = item_resource_index;
+        }
+
+        // The span of the project itself, before the chart is widened to whole months
+        let (project_start_date, project_end_date) = (start_date, end_date);
+        let calendar_days = (end_date - start_date).num_days();
+        let working_days = start_date
+            .iter_days()
+            .take(calendar_days.max(0) as usize)
+            .filter(|date| layout.calendar_days || !is_weekend(date))
+            .count();
+
+        // Allow the chart bounds to be pinned, items can run past the end but not before the start
+        if let Some(chart_start) = chart_data.chart_start {
+            if chart_start > start_date {
+                bail!(
+                    "Chart start {} is after the first item start {}",
+                    chart_start,
+                    start_date
+                );
+            }
+
+            start_date = chart_start;
+        }
+
+        if let Some(chart_end) = chart_data.chart_end {
+            if chart_end < end_date {
+                self.warning(
+                    None,
+                    format_args!(
+                        "Chart end {} is before the last item end {}, the items are cut off at the end of its month",
+                        chart_end, end_date
+                    ),
+                );
+            }
+
+            end_date = chart_end;
+        }
+
+        // A window replaces the chart bounds exactly, otherwise they are widened to whole months
+        if let Some((window_start, window_end)) = layout.window {
+            start_date = window_start;
+            end_date = window_end;
+        } else {
+            start_date = NaiveDate::from_ymd_opt(start_date.year(), start_date.month(), 1).unwrap(); // FIXME unwrap
+            end_date = NaiveDate::from_ymd_opt(
+                end_date.year(),
+                end_date.month(),
+                num_days_in_month(end_date.year(), end_date.month()),
+            )
+            .unwrap(); // FIXME unwrap
+
+            // Runway before the first month, as part of the month before
+            start_date = match Duration::try_days(layout.lead_days as i64)
+                .and_then(|lead| start_date.checked_sub_signed(lead))
+            {
+                Some(lead_date) => lead_date,
+                None => bail!("A lead of {} days is out of range", layout.lead_days),
+            };
+        }
+
+        // Map a day offset from the chart start onto the axis, skipping the weekends if compressed
+        let axis_days = |day_offset: f64| -> f64 {
+            if !layout.compress_weekends || day_offset < 0.0 {
+                return day_offset;
+            }
+
+            let whole_days = day_offset.floor() as i64;
+            let working_days = start_date
+                .iter_days()
+                .take(whole_days as usize)
+                .filter(|date| !is_weekend(date))
+                .count() as f64;
+
+            // FIXME unwrap
+            if is_weekend(&(start_date + Duration::try_days(whole_days).unwrap())) {
+                working_days
+            } else {
+                working_days + day_offset.fract()
+            }
+        };
+
+        // Create all the column data
+        let mut all_items_width: f32 = 0.0;
+        let mut cols = vec![];
+        // The number of days each column has on the axis
+        let mut col_days: Vec<u32> = vec![];
+        // The most days a month can have on the axis
+        let max_month_days: f32 = if layout.compress_weekends { 23.0 } else { 31.0 };
+
+        if let Some(px_per_day) = layout.px_per_day {
+            max_month_width = px_per_day * max_month_days;
+        }
+
+        // The days from one date to another, inclusive, that take up room on the axis
+        let num_axis_days = |from: NaiveDate, to: NaiveDate| -> u32 {
+            from.iter_days()
+                .take_while(|date| *date <= to)
+                .filter(|date| !layout.compress_weekends || !is_weekend(date))
+                .count() as u32
+        };
+
+        date = start_date;
+
+        // The first and last columns only have part of their month in a window
+        while date <= end_date {
+            let month_start = NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap(); // FIXME unwrap
+            let month_end = NaiveDate::from_ymd_opt(
+                date.year(),
+                date.month(),
+                num_days_in_month(date.year(), date.month()),
+            )
+            .unwrap(); // FIXME unwrap
+            let item_days = num_axis_days(date, month_end.min(end_date));
+            let item_width = if layout.uniform_months {
+                max_month_width * (item_days as f32)
+                    / (num_axis_days(month_start, month_end) as f32)
+            } else {
+                max_month_width * (item_days as f32) / max_month_days
+            };
+
+            col_days.push(item_days);
+            all_items_width += item_width;
+
+            cols.push(ColumnRenderData {
+                width: item_width,
+                month_name: match self.month_name {
+                    Some(month_name) => month_name(date.year(), date.month()),
+                    None => MONTH_NAMES[date.month() as usize - 1].to_string(),
+                },
+            });
+
+            date = NaiveDate::from_ymd_opt(
+                date.year() + (if date.month() == 12 { 1 } else { 0 }),
+                date.month() % 12 + 1,
+                1,
+            )
+            .unwrap(); // FIXME unwrap
+        }
+
+        let mut resource_index: usize = 0;
+        let row_gutter = Gutter {
+            left: 5.0,
+            top: 5.0,
+            right: 5.0,
+            bottom: 5.0,
+        };
+        // Wrapping a title makes its row taller, to fit all the lines
+        let title_lines: Vec<Vec<String>> = chart_data
+            .items
+            .iter()
+            .map(|item| {
+                if layout.wrap_titles {
+                    let max_chars = ((title_width - row_gutter.width()) / ITEM_CHAR_WIDTH) as usize;
+
+                    GanttChartTool::wrap_title(&item.title, max_chars)
+                } else {
+                    vec![item.title.clone()]
+                }
+            })
+            .collect();
+        // TODO(john): The line height should be configurable, and for the resource table
+        let row_height = row_gutter.height() + ITEM_LINE_HEIGHT;
+        let phase_height = if layout.phase_bands { row_height } else { 0.0 };
+        // Leave room for the title, the subtitle under it, any phases, and the column headings
+        let title_height = if chart_data.subtitle.is_some() {
+            65.0
+        } else {
+            45.0
+        } + phase_height;
+        let gutter = Gutter {
+            left: layout.margins.left,
+            top: layout.margins.top + title_height + row_height + row_gutter.bottom,
+            right: layout.margins.right,
+            bottom: layout.margins.bottom,
+        };
+        let resource_gutter = Gutter {
+            left: 10.0,
+            top: 10.0,
+            right: 10.0,
+            bottom: 10.0,
+        };
+        let resource_height = resource_gutter.height() + 20.0;
+
+        // Shrink the months proportionally if the document would be too wide
+        if let Some(max_width) = layout.max_width {
+            let available_width = max_width - gutter.width() - title_width;
+
+            if available_width <= 0.0 {
+                bail!("Maximum width {} leaves no room for the months", max_width);
+            }
+
+            if all_items_width > available_width {
+                let factor = available_width / all_items_width;
+
+                for col in cols.iter_mut() {
+                    col.width *= factor;
+                }
+            }
+        }
+
+        // Map a day offset from the chart start to an x offset, scaled by the month it falls in
+        let day_x = |day_offset: f64| -> f32 {
+            let mut axis_offset = axis_days(day_offset);
+            let mut x: f32 = 0.0;
+
+            for (i, (col, days)) in cols.iter().zip(col_days.iter()).enumerate() {
+                // Anything outside of the columns is scaled like the nearest one
+                if axis_offset < *days as f64 || i == cols.len() - 1 {
+                    return x + (axis_offset as f32) / (*days as f32) * col.width;
+                }
+
+                axis_offset -= *days as f64;
+                x += col.width;
+            }
+
+            x
+        };
+
+        let mut rows = vec![];
+
+        // Days from the chart start, including any fraction of a day
+        let mut day_offset: f64 = 0.0;
+
+        // Calculate the X offsets of all the bars and milestones
+        for (i, item) in chart_data.items.iter().enumerate() {
+            if let Some(item_start_date) = item.start_date {
+                day_offset = (item_start_date - start_date).num_days() as f64;
+            } else if lag_days[i] != 0 {
+                day_offset = day_offset.floor() + lag_days[i] as f64;
+            }
+
+            let item_start_date = start_date + Duration::try_days(day_offset as i64).unwrap(); // FIXME unwrap
+            let offset = title_width + gutter.left + day_x(day_offset);
+
+            let mut length: Option<f32> = None;
+            let segments: Vec<(f32, f32)> = segment_spans[i]
+                .iter()
+                .map(|(from_days, span_days)| {
+                    (
+                        title_width + gutter.left + day_x(day_offset + from_days),
+                        (day_x(day_offset + from_days + span_days) - day_x(day_offset + from_days))
+                            .max(layout.min_bar_width),
+                    )
+                })
+                .collect();
+
+            if let Some(item_days) = shadow_durations[i] {
+                // Use the shadow duration instead of the actual duration as it accounts for weekends
+                // Only the width is clamped, the bar still starts on its real date
+                length = Some(
+                    (day_x(day_offset + item_days) - day_x(day_offset)).max(layout.min_bar_width),
+                );
+                day_offset += item_days;
+            }
+
+            // The kind decides how the item is drawn, even against its duration, otherwise
+            // anything scheduled without a duration is a milestone, placed like any other item
+            let milestone = match item.kind {
+                Some(kind) => kind == ItemKind::Milestone,
+                None => shadow_durations[i].is_none(),
+            };
+
+            if milestone {
+                length = None;
+            }
+
+            let item_end_date = start_date + Duration::try_days(day_offset as i64).unwrap(); // FIXME unwrap
+
+            // The baseline is scheduled on its own, skipping the weekends in the same way
+            let baseline = match (item.baseline_start, item.baseline_duration) {
+                (Some(baseline_start), Some(baseline_duration)) => {
+                    let unit_days = item
+                        .duration_unit
+                        .unwrap_or(chart_data.duration_unit)
+                        .days();
+                    let (_, shadow_days, _) = skip_weekend(
+                        baseline_start,
+                        0.0,
+                        baseline_duration * unit_days,
+                        layout.calendar_days,
+                    );
+                    let from_days = (baseline_start - start_date).num_days() as f64;
+
+                    Some((
+                        title_width + gutter.left + day_x(from_days),
+                        day_x(from_days + shadow_days) - day_x(from_days),
+                    ))
+                }
+                _ => None,
+            };
+
+            if let Some(item_resource_index) = chart_data.resource_index(item) {
+                resource_index = item_resource_index;
+            }
+
+            rows.push(RowRenderData {
+                height: row_gutter.height() + ITEM_LINE_HEIGHT * title_lines[i].len() as f32,
+                title_lines: title_lines[i].clone(),
+                classes: item.classes.clone(),
+                status: item.status.clone(),
+                title: item.title.clone(),
+                item_index: i,
+                // Items without a start date follow on from the previous item
+                predecessor: if i > 0 && item.start_date.is_none() {
+                    Some(i - 1)
+                } else {
+                    None
+                },
+                resource_index,
+                bar_class: if item.color.is_some() {
+                    classes::item(i)
+                } else {
+                    classes::resource(resource_index)
+                },
+                start_date: item_start_date,
+                end_date: item_end_date,
+                duration: shadow_durations[i],
+                offset,
+                length,
+                milestone,
+                open: item.open.unwrap_or(false),
+                critical: false,
+                slack: 0.0,
+                slack_days: 0,
+                note: item.note.clone(),
+                order: item.order,
+                baseline,
+                segments,
+            });
+        }
+
+        // Walk back from the items finishing last through the items they are chained to
+        if let Some(last_end_date) = rows.iter().map(|row| row.end_date).max() {
+            let mut chained_start_date: Option<NaiveDate> = None;
+
+            for (i, row) in rows.iter_mut().enumerate().rev() {
+                row.critical =
+                    row.end_date == last_end_date || chained_start_date == Some(row.end_date);
+
+                // Only items without an explicit start date are chained to their predecessor
+                chained_start_date = if row.critical && chart_data.items[i].start_date.is_none() {
+                    match Duration::try_days(lag_days[i])
+                        .and_then(|lag| row.start_date.checked_sub_signed(lag))
+                    {
+                        Some(date) => Some(date),
+                        None => bail!(
+                            "Item '{}' has a lag of {} days that is out of range",
+                            row.title,
+                            lag_days[i]
+                        ),
+                    }
+                } else {
+                    None
+                };
+            }
+        }
+
+        // The free slack runs up to the start of the next item if chained to it, otherwise the project end
+        let last_end = rows
+            .iter()
+            .map(|row| (row.offset + row.length.unwrap_or(0.0), row.end_date))
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+
+        for i in 0..rows.len() {
+            let row = &rows[i];
+
+            if row.milestone || row.critical {
+                continue;
+            }
+
+            let (slack_end, slack_end_date) = match rows.get(i + 1) {
+                Some(next_row) if chart_data.items[i + 1].start_date.is_none() => {
+                    (next_row.offset, next_row.start_date)
+                }
+                _ => match last_end {
+                    Some(last_end) => last_end,
+                    None => continue,
+                },
+            };
+            let slack_days = (slack_end_date - row.end_date).num_days().max(0);
+
+            // Going by the days, so that a rounding error in the offsets is not a sliver of slack
+            if slack_days > 0 {
+                rows[i].slack = (slack_end - row.offset - row.length.unwrap_or(0.0)).max(0.0);
+                rows[i].slack_days = slack_days;
+            }
+        }
+
+        // Derive a status from how complete each task is by the day, leaving any given status alone
+        if layout.auto_status {
+            let today = layout
+                .today
+                .or(chart_data.marked_date.as_ref().map(|marked| marked.date()))
+                .unwrap_or_else(|| chrono::Local::now().date_naive());
+
+            for (row, item) in rows.iter_mut().zip(chart_data.items.iter()) {
+                if row.milestone || item.status.is_some() {
+                    continue;
+                }
+
+                if item.percent_complete.unwrap_or(0.0) >= 100.0 {
+                    row.status = Some("done".to_string());
+                } else if row.end_date < today {
+                    row.classes.push(classes::OVERDUE.to_string());
+                }
+            }
+        }
+
+        // A resource can only work on one task at a time
+        for (i, row) in rows.iter().enumerate() {
+            for other_row in rows[i + 1..].iter() {
+                if row.milestone
+                    || other_row.milestone
+                    || row.resource_index != other_row.resource_index
+                    || row.start_date >= other_row.end_date
+                    || other_row.start_date >= row.end_date
+                    || early_starts.contains(&(
+                        row.item_index.min(other_row.item_index),
+                        row.item_index.max(other_row.item_index),
+                    ))
+                {
+                    continue;
+                }
+
+                self.warning(
+                    Some(&other_row.title),
+                    format_args!(
+                        "Resource '{}' is over-allocated from {} to {}, '{}' and '{}' overlap",
+                        chart_data.resources[row.resource_index].name(),
+                        row.start_date.max(other_row.start_date),
+                        row.end_date.min(other_row.end_date),
+                        row.title,
+                        other_row.title
+                    ),
+                );
+            }
+        }
+
+        // Total up the tasks for each resource
+        let mut resources: Vec<ResourceRenderData> = chart_data
+            .resources
+            .iter()
+            .map(|resource| ResourceRenderData {
+                name: resource.name().to_string(),
+                icon: resource.icon().map(str::to_owned),
+                initials: resource.initials().map(str::to_owned),
+                num_tasks: 0,
+                num_days: 0.0,
+                color: 0,
+                light_color: 0,
+            })
+            .collect();
+
+        for (item, row) in chart_data.items.iter().zip(rows.iter()) {
+            if let Some(item_days) = item.days(chart_data) {
+                let resource = &mut resources[row.resource_index];
+
+                resource.num_tasks += 1;
+                resource.num_days += item_days;
+            }
+        }
+
+        // Overall completion, weighting each task by its working days
+        let (done_days, all_days) = chart_data
+            .items
+            .iter()
+            .filter_map(|item| {
+                item.days(chart_data).map(|days| {
+                    (
+                        days * item.percent_complete.unwrap_or(0.0) as f64 / 100.0,
+                        days,
+                    )
+                })
+            })
+            .fold((0.0, 0.0), |(done, all), (item_done, item_all)| {
+                (done + item_done, all + item_all)
+            });
+        let completion = if all_days > 0.0 {
+            (done_days / all_days) as f32
+        } else {
+            0.0
+        };
+
+        // Cut everything down to the window, now that the scheduling is done with the rows
+        if let Some((window_start, window_end)) = layout.window {
+            let left =
+                title_width + gutter.left + day_x((window_start - start_date).num_days() as f64);
+            let right = title_width
                 + gutter.left
-                + ((date - start_date).num_days() as f32) / (num_item_days as f32) * all_items_width
-        });
+                + day_x((window_end - start_date).num_days() as f64 + 1.0);
+            // Bars that only touch the window are outside it, milestones on the edge are not
+            let clip = |(x, width): (f32, f32)| -> Option<(f32, f32)> {
+                let (from, to) = (x.max(left), (x + width).min(right));
 
-        let mut styles: Vec<String> = vec_of_strings![
-            ".outer-lines{ stroke-width:3; stroke:#aaaaaa;}",
-            ".inner-lines{ stroke-width:2; stroke:#dddddd;}",
-            ".item{font-family:Arial; font-size:12pt; dominant-baseline:middle;}",
-            ".resource{font-family:Arial; font-size:12pt; text-anchor:end; dominant-baseline:middle;}",
-            ".title{font-family:Arial; font-size:18pt;}",
-            ".heading{font-family:Arial; font-size:16pt; dominant-baseline:middle; text-anchor:middle;}",
-            ".task-heading{dominant-baseline:middle; text-anchor:start;}",
-            ".milestone{fill:black;stroke-width:1;stroke:black;}",
-            ".marker{stroke-width:2; stroke:#888888; stroke-dasharray:7;}"
-        ];
+                if to < from || (to == from && width > 0.0) {
+                    None
+                } else {
+                    Some((from, to - from))
+                }
+            };
+
+            rows.retain_mut(|row| {
+                let end = row.offset + row.length.unwrap_or(0.0);
+                let Some((offset, length)) = clip((row.offset, row.length.unwrap_or(0.0))) else {
+                    return false;
+                };
+
+                row.slack = clip((end, row.slack)).map_or(0.0, |(_, slack)| slack);
+                row.offset = offset;
+                row.length = row.length.map(|_| length);
+                row.segments = row.segments.iter().copied().filter_map(clip).collect();
+                row.baseline = row.baseline.and_then(clip);
+
+                true
+            });
+        }
+
+        if layout.milestones_only {
+            rows.retain(|row| row.milestone);
+
+            if rows.is_empty() {
+                bail!("There are no milestones to show");
+            }
+        }
+
+        // Each group spans its items, in the order the groups first appear
+        let mut phases: Vec<PhaseRenderData> = vec![];
+
+        if layout.phase_bands {
+            for row in rows.iter() {
+                let Some(ref name) = chart_data.items[row.item_index].group else {
+                    continue;
+                };
+                let (from, to) = (row.offset, row.offset + row.length.unwrap_or(0.0));
+
+                match phases.iter_mut().find(|phase| phase.name == *name) {
+                    Some(phase) => {
+                        let end = (phase.offset + phase.length).max(to);
+
+                        phase.offset = phase.offset.min(from);
+                        phase.length = end - phase.offset;
+                    }
+                    None => phases.push(PhaseRenderData {
+                        name: name.clone(),
+                        offset: from,
+                        length: to - from,
+                    }),
+                }
+            }
+        }
+
+        // Count the tasks on each day of the chart, milestones take no time
+        let utilization: Vec<UtilizationRenderData> = if layout.utilization {
+            start_date
+                .iter_days()
+                .take_while(|date| *date <= end_date)
+                .enumerate()
+                .filter_map(|(i, date)| {
+                    let mut counts = vec![0; chart_data.resources.len()];
+
+                    for row in rows.iter() {
+                        if !row.milestone && row.start_date <= date && date < row.end_date {
+                            counts[row.resource_index] += 1;
+                        }
+                    }
+
+                    let width = day_x(i as f64 + 1.0) - day_x(i as f64);
+
+                    (width > 0.0 && counts.iter().any(|count| *count > 0)).then(|| {
+                        UtilizationRenderData {
+                            offset: title_width + gutter.left + day_x(i as f64),
+                            width,
+                            counts,
+                        }
+                    })
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let project_offsets = (
+            title_width + gutter.left + day_x((project_start_date - start_date).num_days() as f64),
+            title_width + gutter.left + day_x((project_end_date - start_date).num_days() as f64),
+        );
+
+        let marked_date_offset = chart_data
+            .marked_date
+            .as_ref()
+            .map(|marked| marked.date())
+            .map(|date| title_width + gutter.left + day_x((date - start_date).num_days() as f64));
+
+        // Locate every weekend day, using the same scaling as the month columns
+        let weekend_offsets: Vec<(f32, f32)> = start_date
+            .iter_days()
+            .take_while(|date| *date <= end_date)
+            .enumerate()
+            .filter(|(_, date)| !layout.compress_weekends && is_weekend(date))
+            .map(|(i, _)| {
+                (
+                    title_width + gutter.left + day_x(i as f64),
+                    day_x(i as f64 + 1.0) - day_x(i as f64),
+                )
+            })
+            .collect();
+        let week_offsets: Vec<(f32, u32)> = start_date
+            .iter_days()
+            .take_while(|date| *date <= end_date)
+            .enumerate()
+            .filter(|(_, date)| date.weekday() == layout.week_start.weekday())
+            .map(|(i, date)| {
+                (
+                    title_width + gutter.left + day_x(i as f64),
+                    layout.week_start.week_number(date),
+                )
+            })
+            .collect();
+
+        let mut styles: Vec<String> = [
+            (classes::OUTER_LINES, "stroke-width:3; stroke:#aaaaaa;"),
+            (classes::INNER_LINES, "stroke-width:2; stroke:#dddddd;"),
+            (
+                classes::ITEM,
+                "font-family:Arial; font-size:12pt; dominant-baseline:middle;",
+            ),
+            (
+                classes::RESOURCE,
+                "font-family:Arial; font-size:12pt; text-anchor:end; dominant-baseline:middle;",
+            ),
+            (classes::TITLE, "font-family:Arial; font-size:18pt;"),
+            (
+                classes::RESOURCE_INITIALS,
+                "font-family:Arial; font-size:7pt; font-weight:bold; fill:#ffffff; text-anchor:middle; dominant-baseline:central;",
+            ),
+            (
+                classes::SUBTITLE,
+                "font-family:Arial; font-size:12pt; fill:#555555;",
+            ),
+            (
+                classes::HEADING,
+                "font-family:Arial; font-size:16pt; dominant-baseline:middle; text-anchor:middle;",
+            ),
+            (
+                classes::TASK_HEADING,
+                "dominant-baseline:middle; text-anchor:start;",
+            ),
+            (
+                classes::MILESTONE,
+                "fill:black;stroke-width:1;stroke:black;",
+            ),
+            (
+                classes::MARKER,
+                "stroke-width:2; stroke:#888888; stroke-dasharray:7;",
+            ),
+            (classes::ROW_STRIPE, "fill:#f4f4f4;"),
+            (classes::WEEKEND_BAND, "fill:#ececec;"),
+            (classes::WEEKEND_MARK, "fill:#bbbbbb;"),
+            (classes::BAR_LABEL, "fill:#ffffff;"),
+            (
+                classes::CRITICAL,
+                "fill:none; stroke-width:3; stroke:#d62728;",
+            ),
+            (classes::BACKGROUND, "fill:#ffffff;"),
+            (classes::WEEK_LINE, "stroke-width:1; stroke:#e4e4e4;"),
+            (
+                classes::TASK_GUIDE,
+                "stroke-width:1; stroke:#bbbbbb; stroke-dasharray:2;",
+            ),
+            (classes::SUMMARY, "fill:#555555;"),
+            (
+                classes::SUMMARY_TRACK,
+                "stroke-width:1; stroke:#555555; fill:none;",
+            ),
+            (classes::OVERFLOW, "stroke-dasharray:4 2;"),
+            (classes::BASELINE, "fill:#999999;"),
+            (classes::SLACK, "fill:#cccccc; fill-opacity:0.6;"),
+            (
+                classes::DURATION,
+                "font-family:Arial; font-size:10pt; fill:#555555; text-anchor:end;",
+            ),
+            (
+                classes::DEPENDENCY_ARROW,
+                "fill:none; stroke-width:1; stroke:#555555;",
+            ),
+            (classes::DEPENDENCY_ARROW_HEAD, "fill:#555555;"),
+            (classes::OVERFLOW_ARROW, "fill:#888888;"),
+            (
+                classes::NOTE,
+                "font-family:Arial; font-size:9pt; fill:#888888; dominant-baseline:middle;",
+            ),
+            (
+                classes::WEEK_LABEL,
+                "font-family:Arial; font-size:8pt; fill:#888888; text-anchor:middle;",
+            ),
+            (
+                classes::MARKER_LABEL,
+                "font-family:Arial; font-size:9pt; fill:#888888; dominant-baseline:hanging;",
+            ),
+            (
+                classes::STATUS,
+                "font-family:Arial; font-size:10pt; fill:#888888; text-anchor:middle; dominant-baseline:central;",
+            ),
+            (
+                classes::LEGEND_ENTRY,
+                "font-family:Arial; font-size:9pt; dominant-baseline:middle;",
+            ),
+            (
+                classes::PHASE_LABEL,
+                "font-family:Arial; font-size:9pt; fill:#333333; text-anchor:middle; dominant-baseline:middle;",
+            ),
+            (
+                classes::AXIS_DATE,
+                "font-family:Arial; font-size:8pt; fill:#888888; text-anchor:middle; dominant-baseline:hanging;",
+            ),
+        ]
+        .iter()
+        .map(|(class, rule)| format!(".{class}{{{rule}}}"))
+        .collect();
+
+        for (status, color) in [
+            ("done", "#2ca02c"),
+            ("blocked", "#d62728"),
+            ("at-risk", "#ff7f0e"),
+        ] {
+            styles.push(format!(".{}{{fill:{color};}}", classes::status(status)));
+        }
 
         // Generate random resource colors based on https://martin.ankerl.com/2009/12/09/how-to-create-random-colors-programmatically/
-        let mut rng = rand::thread_rng();
-        let mut h: f32 = rng.gen();
+        let mut rng = match layout.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut h: f32 = layout.hue_start.unwrap_or_else(|| rng.gen());
+
+        for (i, resource) in resources.iter_mut().enumerate() {
+            let rgb = GanttChartTool::hsv_to_rgb(h, layout.color_saturation, layout.color_value);
+
+            resource.color = rgb;
+            resource.light_color = GanttChartTool::hsv_to_rgb(h, 0.35, 0.8);
+
+            styles.push(format!(
+                ".{}{{stroke-width:1; stroke:#{rgb:06x}; fill:#{rgb:06x};}}",
+                classes::bar(&classes::resource(i), false)
+            ));
+            styles.push(format!(
+                ".{}{{stroke-width:2; stroke:#{rgb:06x}; fill:none;}}",
+                classes::bar(&classes::resource(i), true)
+            ));
+            styles.push(format!(
+                ".{}{{stroke:#{rgb:06x}; fill:#{rgb:06x};}}",
+                classes::milestone(i)
+            ));
+            styles.push(format!(
+                ".{}{{fill:#{:06x};}}",
+                classes::swimlane(i),
+                GanttChartTool::hsv_to_rgb(h, 0.15, 0.97)
+            ));
 
-        for i in 0..chart_data.resources.len() {
-            let rgb = GanttChartTool::hsv_to_rgb(h, 0.5, 0.5);
+            h = (h + GOLDEN_RATIO_CONJUGATE) % 1.0;
+        }
 
+        // The phases carry on along the same walk, in lighter colors
+        for i in 0..phases.len() {
             styles.push(format!(
-                ".resource-{i}-closed{{stroke-width:1; stroke:#{rgb:06x}; fill:#{rgb:06x};}}"
+                ".{}{{fill:#{:06x};}}",
+                classes::phase(i),
+                GanttChartTool::hsv_to_rgb(h, 0.25, 0.9)
             ));
+
+            h = (h + GOLDEN_RATIO_CONJUGATE) % 1.0;
+        }
+
+        // Items with their own color override the resource color
+        for (i, item) in chart_data.items.iter().enumerate() {
+            // The colors have all been checked, so this is every item with a color
+            if let Some(rgb) = item.color.as_deref().and_then(colors::parse) {
+                let color = format!("#{rgb:06x}");
+
+                styles.push(format!(
+                    ".{}{{stroke-width:1; stroke:{color}; fill:{color};}}",
+                    classes::bar(&classes::item(i), false)
+                ));
+                styles.push(format!(
+                    ".{}{{stroke-width:2; stroke:{color}; fill:none;}}",
+                    classes::bar(&classes::item(i), true)
+                ));
+            }
+        }
+
+        // After the resource and item colors so that it wins over them
+        if layout.auto_status {
             styles.push(format!(
-                ".resource-{i}-open{{stroke-width:2; stroke:#{rgb:06x}; fill:none;}}"
+                ".{}{{stroke:#d62728; fill:#d62728;}}",
+                classes::OVERDUE
             ));
-
-            h = (h + GOLDEN_RATIO_CONJUGATE) % 1.0;
         }
 
+        // Anything else goes last, so that it can override the rules above
+        styles.extend(layout.extra_styles.iter().cloned());
+
         Ok(RenderData {
             title: chart_data.title.to_owned(),
+            subtitle: chart_data.subtitle.clone(),
+            start_date,
+            end_date,
             gutter,
             row_gutter,
             row_height,
@@ -466,119 +3617,874 @@ impl<'a> GanttChartTool<'a> {
             resource_height,
             styles,
             title_width,
-            max_month_width,
+            marked_date: chart_data.marked_date.as_ref().map(|marked| marked.date()),
+            marked_date_label: chart_data
+                .marked_date
+                .as_ref()
+                .and_then(|marked| marked.label())
+                .map(str::to_string),
+            calendar_days,
+            project_start_date,
+            project_end_date,
+            project_offsets,
+            working_days,
             marked_date_offset,
-            rect_corner_radius: 3.0,
+            date_format: layout.date_format.clone(),
+            completion,
+            source: None,
+            weekend_offsets,
+            week_offsets,
+            phase_height,
+            single_band: layout.milestones_only,
+            phases,
+            utilization,
+            rect_corner_radius: layout.corner_radius,
             cols,
             rows,
-            resources: chart_data.resources.clone(),
+            resources,
         })
     }
 
-    fn render_chart(&self, use_legend: bool, chart: &RenderData) -> Result<String, Box<dyn Error>> {
+    fn build_schedule(chart: &RenderData) -> ScheduleData {
+        ScheduleData {
+            title: chart.title.clone(),
+            start_date: chart.start_date,
+            end_date: chart.end_date,
+            resources: chart
+                .resources
+                .iter()
+                .map(|resource| resource.name.clone())
+                .collect(),
+            resource_colors: chart
+                .resource_colors()
+                .into_iter()
+                .map(|(name, rgb)| ScheduleResourceColor {
+                    name,
+                    color: format!("#{rgb:06x}"),
+                })
+                .collect(),
+            items: chart
+                .rows
+                .iter()
+                .map(|row| ScheduleItemData {
+                    title: row.title.clone(),
+                    resource_index: row.resource_index,
+                    start_date: row.start_date,
+                    end_date: row.end_date,
+                    duration: row.duration,
+                    milestone: row.milestone,
+                    open: row.open,
+                    slack_days: row.slack_days,
+                })
+                .collect(),
+        }
+    }
+
+    fn render_chart(
+        &self,
+        options: &RenderOptions,
+        chart: &RenderData,
+    ) -> Result<String, Box<dyn Error>> {
+        // The top of each row, and the bottom of the last one, as the rows can differ in height
+        let row_ys: Vec<f32> = if chart.single_band {
+            vec![chart.gutter.top; chart.rows.len()]
+                .into_iter()
+                .chain(std::iter::once(chart.gutter.top + chart.row_height))
+                .collect()
+        } else {
+            std::iter::once(chart.gutter.top)
+                .chain(chart.rows.iter().scan(chart.gutter.top, |y, row| {
+                    *y += row.height;
+                    Some(*y)
+                }))
+                .collect()
+        };
+        // The summary goes in an extra row after the items
+        let rows_bottom = row_ys[chart.rows.len()]
+            + if options.summary {
+                chart.row_height
+            } else {
+                0.0
+            };
         let width: f32 = chart.gutter.left
             + chart.title_width
             + chart.cols.iter().map(|col| col.width).sum::<f32>()
             + chart.gutter.right;
-        let height = chart.gutter.top
-            + (chart.rows.len() as f32 * chart.row_height)
-            + (if use_legend {
+        // The project dates go in a strip under the rows
+        let bounds_height = if options.show_bounds { 15.0 } else { 0.0 };
+        // Each task on a day adds a step to the utilization, with the busiest day setting the height
+        let max_utilization = chart
+            .utilization
+            .iter()
+            .map(|day| day.counts.iter().sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let utilization_height = if max_utilization > 0 {
+            max_utilization as f32 * UTILIZATION_STEP + chart.row_gutter.height()
+        } else {
+            0.0
+        };
+        let height = rows_bottom
+            + bounds_height
+            + utilization_height
+            + (if options.legend == LegendPosition::Bottom {
                 chart.resource_gutter.height() + chart.row_height
             } else {
                 0.0
             })
             + chart.gutter.bottom;
 
-        let mut doc = Document::new()
-            .set("width", width)
-            .set("height", height)
-            .set("viewBox", (0, 0, width, height))
-            .set("style", "background-color: white;");
+        // Everything goes in one group so it can be output without the document
+        let mut chart_g = Group::new()
+            .set("class", options.class(classes::CHART))
+            .set("xmlns:inkscape", INKSCAPE_NAMESPACE);
+        // Named layers for when the chart is opened in a drawing program
+        let layer = |id: &str, label: &str| {
+            Group::new()
+                .set("id", options.class(id))
+                .set("inkscape:groupmode", "layer")
+                .set("inkscape:label", label)
+        };
+
+        // Summarize the chart for screen readers
+        let num_milestones = chart.rows.iter().filter(|row| row.milestone).count();
+        chart_g.append(Description::new().add(TextNode::new(format!(
+            "{} tasks and {} milestones from {} to {}",
+            chart.rows.len() - num_milestones,
+            num_milestones,
+            chart.format_date(chart.start_date),
+            chart.format_date(chart.end_date)
+        ))));
+
+        // A CDATA section can hold anything but its own terminator, which is split in two
+        if let Some(ref source) = chart.source {
+            let mut metadata = Element::new("metadata");
+            metadata.append(Blob::new(format!(
+                "<![CDATA[{}]]>",
+                source.replace("]]>", "]]]]><![CDATA[>")
+            )));
+            chart_g.append(metadata);
+        }
 
         let mut style = Style::new("");
         for s in chart.styles.iter() {
-            style.append(Blob::new(s));
+            style.append(Blob::new(options.style(s)));
         }
 
-        doc.append(style);
+        // Mirror the x coordinates and text anchors for right-to-left layout
+        let mirror_x = |x: f32| if options.rtl { width - x } else { x };
+        let mirror_rect_x = |x: f32, w: f32| if options.rtl { width - x - w } else { x };
+
+        if options.rtl {
+            for (class, anchor) in [
+                (classes::ITEM, "end"),
+                (classes::RESOURCE, "start"),
+                (classes::TASK_HEADING, "end"),
+                (classes::TITLE, "end"),
+                (classes::SUBTITLE, "end"),
+                (classes::NOTE, "end"),
+                (classes::DURATION, "start"),
+                (classes::LEGEND_ENTRY, "end"),
+                (classes::MARKER_LABEL, "end"),
+            ] {
+                style.append(Blob::new(
+                    options.style(&format!(".{class}{{text-anchor:{anchor};}}")),
+                ));
+            }
+        }
+
+        // An explicit title alignment overrides the reading direction
+        if let Some(align) = options.title_align {
+            let anchor = match align {
+                TitleAlign::Left => "start",
+                TitleAlign::Center => "middle",
+                TitleAlign::Right => "end",
+            };
+
+            for class in [classes::TITLE, classes::SUBTITLE] {
+                style.append(Blob::new(
+                    options.style(&format!(".{class}{{text-anchor:{anchor};}}")),
+                ));
+            }
+        }
+
+        // Point the closed resource bars at their gradients, which come after the flat fills
+        if options.bar_style == BarStyle::Gradient {
+            let mut defs = Definitions::new();
+
+            for (i, resource) in chart.resources.iter().enumerate() {
+                let id = options.class(&classes::gradient(i));
+
+                defs.append(
+                    LinearGradient::new()
+                        .set("id", id.as_str())
+                        .set("x1", 0)
+                        .set("y1", 0)
+                        .set("x2", 0)
+                        .set("y2", 1)
+                        .add(
+                            Stop::new()
+                                .set("offset", 0)
+                                .set("stop-color", format!("#{:06x}", resource.light_color)),
+                        )
+                        .add(
+                            Stop::new()
+                                .set("offset", 1)
+                                .set("stop-color", format!("#{:06x}", resource.color)),
+                        ),
+                );
+                style.append(Blob::new(options.style(&format!(
+                    ".{}{{fill:url(#{id});}}",
+                    classes::bar(&classes::resource(i), false)
+                ))));
+            }
+
+            chart_g.append(defs);
+        }
+
+        chart_g.append(style);
 
-        // Render rows
-        let mut rows_g = Group::new();
         let x1 = chart.gutter.left;
         let x2 = width - chart.gutter.right;
-        for (i, row) in chart.rows.iter().enumerate() {
-            let y = chart.gutter.top + (i as f32 * chart.row_height);
-            let line_class = if i == 0 { "outer-lines" } else { "inner-lines" };
 
-            rows_g.append(
-                Text::new(&row.title)
-                    .set("class", "item")
-                    .set("x", chart.gutter.left + chart.row_gutter.left)
-                    .set("y", y + chart.row_gutter.top + chart.row_height / 2.0),
-            );
+        // Render row stripes, before anything else so they stay in the background
+        if options.zebra {
+            let mut stripes_g = layer("row-stripes", "Row stripes");
+            for i in (1..chart.rows.len()).step_by(2) {
+                stripes_g.append(
+                    Rectangle::new()
+                        .set("class", options.class(classes::ROW_STRIPE))
+                        .set("x", options.round(mirror_rect_x(x1, x2 - x1)))
+                        .set("y", options.round(row_ys[i]))
+                        .set("width", options.round(x2 - x1))
+                        .set("height", options.round(chart.rows[i].height)),
+                );
+            }
 
-            // Is this a task or a milestone?
-            if let Some(length) = row.length {
-                // task
-                let bar_class = format!(
-                    "resource-{}{}",
-                    row.resource_index,
-                    if row.open { "-open" } else { "-closed" }
+            chart_g.append(stripes_g);
+        }
+
+        // One band behind each run of consecutive rows with the same resource
+        if options.swimlanes {
+            let mut lanes_g = layer("swimlanes", "Swimlanes");
+            let mut first_row = 0;
+
+            for i in 1..=chart.rows.len() {
+                if i < chart.rows.len()
+                    && chart.rows[i].resource_index == chart.rows[first_row].resource_index
+                {
+                    continue;
+                }
+
+                lanes_g.append(
+                    Rectangle::new()
+                        .set(
+                            "class",
+                            options.class(&classes::swimlane(chart.rows[first_row].resource_index)),
+                        )
+                        .set("x", options.round(mirror_rect_x(x1, x2 - x1)))
+                        .set("y", options.round(row_ys[first_row]))
+                        .set("width", options.round(x2 - x1))
+                        .set("height", options.round(row_ys[i] - row_ys[first_row])),
                 );
-                rows_g.append(
+                first_row = i;
+            }
+
+            chart_g.append(lanes_g);
+        }
+
+        // Render weekend bands, also behind the bars
+        if options.weekend_bands {
+            let mut bands_g = layer("weekends", "Weekends");
+            let height = rows_bottom - chart.gutter.top;
+            for (offset, day_width) in chart.weekend_offsets.iter() {
+                bands_g.append(
+                    Rectangle::new()
+                        .set("class", options.class(classes::WEEKEND_BAND))
+                        .set("x", options.round(mirror_rect_x(*offset, *day_width)))
+                        .set("y", options.round(chart.gutter.top))
+                        .set("width", options.round(*day_width))
+                        .set("height", options.round(height)),
+                );
+            }
+
+            chart_g.append(bands_g);
+        }
+
+        // A short mark for each weekend day, just above the rows
+        if options.weekend_marks {
+            let mut marks_g = layer("weekend-marks", "Weekend Marks");
+
+            for (offset, day_width) in chart.weekend_offsets.iter() {
+                // A little narrower than the day, so that Saturday and Sunday stay apart
+                let mark_width = (day_width - 1.0).max(1.0);
+
+                marks_g.append(
+                    Rectangle::new()
+                        .set("class", options.class(classes::WEEKEND_MARK))
+                        .set(
+                            "x",
+                            options.round(mirror_rect_x(
+                                offset + (day_width - mark_width) / 2.0,
+                                mark_width,
+                            )),
+                        )
+                        .set("y", options.round(chart.gutter.top - 5.0))
+                        .set("width", options.round(mark_width))
+                        .set("height", options.round(3.0)),
+                );
+            }
+
+            chart_g.append(marks_g);
+        }
+
+        // Render week lines, also behind the bars
+        if options.week_lines {
+            let mut weeks_g = layer("weeks", "Weeks");
+            let y2 = rows_bottom;
+            for (offset, week) in chart.week_offsets.iter() {
+                weeks_g.append(
+                    Line::new()
+                        .set("class", options.class(classes::WEEK_LINE))
+                        .set("x1", options.round(mirror_x(*offset)))
+                        .set("y1", options.round(chart.gutter.top))
+                        .set("x2", options.round(mirror_x(*offset)))
+                        .set("y2", options.round(y2)),
+                );
+
+                if options.week_numbers {
+                    weeks_g.append(
+                        Text::new(format!("W{}", week))
+                            .set("class", options.class(classes::WEEK_LABEL))
+                            .set("x", options.round(mirror_x(*offset)))
+                            .set("y", options.round(chart.gutter.top - 4.0)),
+                    );
+                }
+            }
+
+            chart_g.append(weeks_g);
+        }
+
+        // A title that is cut short keeps the whole of it in a tooltip
+        let title_text = |title: &str| match options.max_title_chars {
+            Some(max_chars) if title.chars().count() > max_chars => Text::new(format!(
+                "{}…",
+                title
+                    .chars()
+                    .take(max_chars - 1)
+                    .collect::<String>()
+                    .trim_end()
+            ))
+            .add(Title::new(title)),
+            _ => Text::new(title),
+        };
+
+        // Render rows
+        // A list of the rows for screen readers, with each row an item of it
+        let mut rows_g = layer("rows", "Rows").set("role", "list");
+        for (i, row) in chart.rows.iter().enumerate() {
+            let y = row_ys[i];
+            // Bars stay the usual height, in the middle of a taller row
+            let bar_height = chart.row_height - chart.row_gutter.height();
+            let bar_y = y + (row.height - chart.row_height) / 2.0 + chart.row_gutter.top;
+            let mut row_g = Group::new().set("role", "listitem");
+            let line_class = if i == 0 {
+                classes::OUTER_LINES
+            } else {
+                classes::INNER_LINES
+            };
+
+            if chart.has_title_column() {
+                let title_x = mirror_x(chart.gutter.left + chart.row_gutter.left);
+
+                if row.title_lines.len() > 1 {
+                    // The lines are centered where a single line would be on the row as a whole
+                    let first_y = y + chart.row_gutter.top + row.height / 2.0
+                        - (row.title_lines.len() - 1) as f32 * ITEM_LINE_HEIGHT / 2.0;
+                    let mut title = Text::new("").set("class", options.class(classes::ITEM));
+
+                    for (line_index, line) in row.title_lines.iter().enumerate() {
+                        title.append(
+                            TSpan::new(line.as_str())
+                                .set("x", options.round(title_x))
+                                .set(
+                                    "y",
+                                    options.round(first_y + line_index as f32 * ITEM_LINE_HEIGHT),
+                                ),
+                        );
+                    }
+
+                    row_g.append(title);
+                } else {
+                    row_g.append(
+                        title_text(&row.title)
+                            .set("class", options.class(classes::ITEM))
+                            .set("x", options.round(title_x))
+                            .set(
+                                "y",
+                                options.round(y + chart.row_gutter.top + row.height / 2.0),
+                            ),
+                    );
+                }
+            }
+
+            // Guides at the start and end of a bar, or the center of a milestone, behind it
+            if options.task_guides {
+                let guide_xs = if row.milestone {
+                    vec![row.offset]
+                } else {
+                    vec![row.offset, row.offset + row.length.unwrap_or(0.0)]
+                };
+
+                for x in guide_xs {
+                    row_g.append(
+                        Line::new()
+                            .set("class", options.class(classes::TASK_GUIDE))
+                            .set("x1", options.round(mirror_x(x)))
+                            .set("y1", options.round(y))
+                            .set("x2", options.round(mirror_x(x)))
+                            .set("y2", options.round(y + row.height)),
+                    );
+                }
+            }
+
+            // A thin bar in the bottom of the row gutter
+            if let (true, Some((baseline_offset, baseline_length))) =
+                (options.baseline, row.baseline)
+            {
+                row_g.append(
                     Rectangle::new()
-                        .set("class", bar_class)
-                        .set("x", row.offset)
-                        .set("y", y + chart.row_gutter.top)
-                        .set("rx", chart.rect_corner_radius)
-                        .set("ry", chart.rect_corner_radius)
-                        .set("width", length)
-                        .set("height", chart.row_height - chart.row_gutter.height()),
+                        .set("class", options.class(classes::BASELINE))
+                        .set(
+                            "x",
+                            options.round(mirror_rect_x(baseline_offset, baseline_length)),
+                        )
+                        .set(
+                            "y",
+                            options.round(y + row.height - chart.row_gutter.bottom + 1.0),
+                        )
+                        .set("width", options.round(baseline_length))
+                        .set("height", options.round(chart.row_gutter.bottom - 2.0)),
                 );
+            }
+
+            // Where the note can start, after everything else on the row
+            let mut note_x;
+
+            // Is this a task or a milestone?
+            if !row.milestone {
+                let length = row.length.unwrap_or(0.0);
+                note_x = row.offset + length + chart.row_gutter.left;
+
+                // task, which may be split into several bars
+                let bar_class = classes::bar(&row.bar_class, row.open);
+                let bars = if row.segments.is_empty() {
+                    vec![(row.offset, length)]
+                } else {
+                    row.segments.clone()
+                };
+
+                // Bars are cut off at the end of the chart, with an arrow to show they continue
+                let mut overflows = false;
+
+                for (bar_offset, bar_length) in bars {
+                    if bar_offset >= x2 {
+                        overflows = true;
+                        continue;
+                    }
+
+                    let mut bar_classes = vec![bar_class.as_str()];
+
+                    if bar_offset + bar_length > x2 {
+                        overflows = true;
+                        bar_classes.push(classes::OVERFLOW);
+                    }
+
+                    bar_classes.extend(row.classes.iter().map(String::as_str));
+
+                    let bar_class = options.classes(&bar_classes);
+                    let bar_length = bar_length.min(x2 - bar_offset);
+
+                    row_g.append(
+                        Rectangle::new()
+                            .set("class", bar_class)
+                            .set("x", options.round(mirror_rect_x(bar_offset, bar_length)))
+                            .set("y", options.round(bar_y))
+                            .set("rx", options.round(chart.rect_corner_radius))
+                            .set("ry", options.round(chart.rect_corner_radius))
+                            .set("width", options.round(bar_length))
+                            .set("height", options.round(bar_height)),
+                    );
+
+                    if options.critical_path && row.critical {
+                        row_g.append(
+                            Rectangle::new()
+                                .set("class", options.class(classes::CRITICAL))
+                                .set("x", options.round(mirror_rect_x(bar_offset, bar_length)))
+                                .set("y", options.round(bar_y))
+                                .set("rx", options.round(chart.rect_corner_radius))
+                                .set("ry", options.round(chart.rect_corner_radius))
+                                .set("width", options.round(bar_length))
+                                .set("height", options.round(bar_height)),
+                        );
+                    }
+                }
+
+                // A faint extension, thinner than the bar, up to where the slack runs out
+                if options.show_slack && row.slack > 0.0 && row.offset + length < x2 {
+                    let slack = row.slack.min(x2 - row.offset - length);
+
+                    row_g.append(
+                        Rectangle::new()
+                            .set("class", options.class(classes::SLACK))
+                            .set(
+                                "x",
+                                options.round(mirror_rect_x(row.offset + length, slack)),
+                            )
+                            .set("y", options.round(bar_y + bar_height / 4.0))
+                            .set("width", options.round(slack))
+                            .set("height", options.round(bar_height / 2.0)),
+                    );
+
+                    note_x += slack;
+                }
+
+                if overflows {
+                    row_g.append(
+                        Path::new()
+                            .set("class", options.class(classes::OVERFLOW_ARROW))
+                            .set(
+                                "d",
+                                Data::new()
+                                    .move_to(options.round_point((mirror_x(x2), bar_y)))
+                                    .line_to(options.round_point((
+                                        mirror_x(x2 + chart.gutter.right * 0.8),
+                                        bar_y + bar_height / 2.0,
+                                    )))
+                                    .line_to(
+                                        options.round_point((mirror_x(x2), bar_y + bar_height)),
+                                    )
+                                    .close(),
+                            ),
+                    );
+                }
+
+                // Put the title on the bar itself when there is no title column
+                if !chart.has_title_column() {
+                    row_g.append(
+                        title_text(&row.title)
+                            .set(
+                                "class",
+                                if row.open {
+                                    options.class(classes::ITEM)
+                                } else {
+                                    options.classes(&[classes::ITEM, classes::BAR_LABEL])
+                                },
+                            )
+                            .set(
+                                "x",
+                                options.round(mirror_x(row.offset + chart.row_gutter.left)),
+                            )
+                            .set("y", options.round(y + row.height / 2.0)),
+                    );
+                }
             } else {
                 // milestone
-                let n = (chart.row_height - chart.row_gutter.height()) / 2.0;
-                rows_g.append(
-                    Path::new().set(
-                        "d",
-                        Data::new()
-                            .move_to((row.offset - n, y + chart.row_gutter.top + n))
-                            .line_by((n, -n))
-                            .line_by((n, n))
-                            .line_by((-n, n))
-                            .line_by((-n, -n))
-                            .close(),
+                let half_height = bar_height / 2.0;
+                let n = half_height * options.milestone_size;
+                let cx = mirror_x(row.offset);
+                let cy = bar_y + half_height;
+                let milestone_class = classes::milestone(row.resource_index);
+                let class = options.classes(
+                    &[classes::MILESTONE, milestone_class.as_str()]
+                        .into_iter()
+                        .chain(row.classes.iter().map(String::as_str))
+                        .collect::<Vec<_>>(),
+                );
+
+                match options.milestone_shape {
+                    MilestoneShape::Diamond => row_g.append(
+                        Path::new().set("class", class).set(
+                            "d",
+                            Data::new()
+                                .move_to(options.round_point((cx - n, cy)))
+                                .line_by(options.round_point((n, -n)))
+                                .line_by(options.round_point((n, n)))
+                                .line_by(options.round_point((-n, n)))
+                                .line_by(options.round_point((-n, -n)))
+                                .close(),
+                        ),
+                    ),
+                    MilestoneShape::Circle => row_g.append(
+                        Circle::new()
+                            .set("class", class)
+                            .set("cx", options.round(cx))
+                            .set("cy", options.round(cy))
+                            .set("r", options.round(n)),
                     ),
+                    MilestoneShape::Star => {
+                        // Five points, alternating between the outer and inner radius
+                        let mut data = Data::new();
+
+                        for k in 0..10 {
+                            let angle = std::f32::consts::PI * (k as f32 / 5.0 - 0.5);
+                            let r = if k % 2 == 0 { n } else { n * 0.4 };
+                            let point = (cx + r * angle.cos(), cy + r * angle.sin());
+
+                            data = if k == 0 {
+                                data.move_to(options.round_point(point))
+                            } else {
+                                data.line_to(options.round_point(point))
+                            };
+                        }
+
+                        row_g.append(Path::new().set("class", class).set("d", data.close()));
+                    }
+                }
+
+                // Label the milestone when there is no title column to show it
+                if !chart.has_title_column() {
+                    row_g.append(
+                        title_text(&row.title)
+                            .set("class", options.class(classes::ITEM))
+                            .set(
+                                "x",
+                                options.round(mirror_x(row.offset + n + chart.row_gutter.left)),
+                            )
+                            .set("y", options.round(cy)),
+                    );
+                }
+
+                note_x = row.offset
+                    + n
+                    + chart.row_gutter.left
+                    + if chart.has_title_column() {
+                        0.0
+                    } else {
+                        row.title.chars().count() as f32 * ITEM_CHAR_WIDTH + chart.row_gutter.left
+                    };
+            }
+
+            // The status goes just after the bar or milestone, pushing the note along
+            if let Some(ref status) = row.status {
+                let status_x = note_x + STATUS_WIDTH / 2.0;
+                let status_y = bar_y + bar_height / 2.0;
+                let class = options.classes(&[classes::STATUS, &classes::status(status)]);
+
+                if status_x + STATUS_WIDTH / 2.0 <= width - chart.gutter.right {
+                    match GanttChartTool::status_glyph(status) {
+                        Some(glyph) => row_g.append(
+                            Text::new(glyph)
+                                .set("class", class)
+                                .set("x", options.round(mirror_x(status_x)))
+                                .set("y", options.round(status_y)),
+                        ),
+                        None => row_g.append(
+                            Circle::new()
+                                .set("class", class)
+                                .set("cx", options.round(mirror_x(status_x)))
+                                .set("cy", options.round(status_y))
+                                .set("r", options.round(STATUS_WIDTH / 3.0)),
+                        ),
+                    }
+                }
+
+                note_x += STATUS_WIDTH + chart.row_gutter.left;
+            }
+
+            // Truncate the note to fit before the right hand edge of the chart
+            if let Some(ref note) = row.note {
+                let max_chars =
+                    ((width - chart.gutter.right - note_x) / NOTE_CHAR_WIDTH).max(0.0) as usize;
+                let text: String = if note.chars().count() > max_chars {
+                    note.chars()
+                        .take(max_chars.saturating_sub(1))
+                        .chain(std::iter::once('…'))
+                        .collect()
+                } else {
+                    note.clone()
+                };
+
+                if max_chars > 1 {
+                    row_g.append(
+                        Text::new(text)
+                            .set("class", options.class(classes::NOTE))
+                            .set("x", options.round(mirror_x(note_x)))
+                            .set("y", options.round(y + row.height / 2.0)),
+                    );
+                }
+            }
+
+            // A row sharing the band of the one before has its line already
+            if i == 0 || y > row_ys[i - 1] {
+                row_g.append(
+                    Line::new()
+                        .set("class", options.class(line_class))
+                        .set("x1", options.round(mirror_x(x1)))
+                        .set("y1", options.round(y))
+                        .set("x2", options.round(mirror_x(x2)))
+                        .set("y2", options.round(y)),
                 );
             }
 
+            rows_g.append(row_g);
+        }
+        // Overall completion, as a bar filled in across the span of all the items
+        if options.summary {
+            let y = row_ys[chart.rows.len()];
+            let start_x = chart
+                .rows
+                .iter()
+                .map(|row| row.offset)
+                .fold(f32::MAX, f32::min);
+            let end_x = chart
+                .rows
+                .iter()
+                .map(|row| row.offset + row.length.unwrap_or(0.0))
+                .fold(f32::MIN, f32::max);
+            let label = format!("Overall {:.0}%", chart.completion * 100.0);
+
             rows_g.append(
                 Line::new()
-                    .set("class", line_class)
-                    .set("x1", x1)
-                    .set("y1", y)
-                    .set("x2", x2)
-                    .set("y2", y),
+                    .set("class", options.class(classes::INNER_LINES))
+                    .set("x1", options.round(mirror_x(x1)))
+                    .set("y1", options.round(y))
+                    .set("x2", options.round(mirror_x(x2)))
+                    .set("y2", options.round(y)),
+            );
+
+            if chart.has_title_column() {
+                rows_g.append(
+                    Text::new(&label)
+                        .set("class", options.class(classes::ITEM))
+                        .set(
+                            "x",
+                            options.round(mirror_x(chart.gutter.left + chart.row_gutter.left)),
+                        )
+                        .set(
+                            "y",
+                            options.round(y + chart.row_gutter.top + chart.row_height / 2.0),
+                        ),
+                );
+            }
+
+            rows_g.append(
+                Rectangle::new()
+                    .set("class", options.class(classes::SUMMARY_TRACK))
+                    .set("x", options.round(mirror_rect_x(start_x, end_x - start_x)))
+                    .set("y", options.round(y + chart.row_gutter.top))
+                    .set("rx", options.round(chart.rect_corner_radius))
+                    .set("ry", options.round(chart.rect_corner_radius))
+                    .set("width", options.round(end_x - start_x))
+                    .set(
+                        "height",
+                        options.round(chart.row_height - chart.row_gutter.height()),
+                    ),
+            );
+            rows_g.append(
+                Rectangle::new()
+                    .set("class", options.class(classes::SUMMARY))
+                    .set(
+                        "x",
+                        options.round(mirror_rect_x(start_x, (end_x - start_x) * chart.completion)),
+                    )
+                    .set("y", options.round(y + chart.row_gutter.top))
+                    .set("rx", options.round(chart.rect_corner_radius))
+                    .set("ry", options.round(chart.rect_corner_radius))
+                    .set("width", options.round((end_x - start_x) * chart.completion))
+                    .set(
+                        "height",
+                        options.round(chart.row_height - chart.row_gutter.height()),
+                    ),
             );
+
+            if !chart.has_title_column() {
+                rows_g.append(
+                    Text::new(&label)
+                        .set("class", options.class(classes::ITEM))
+                        .set("x", options.round(mirror_x(end_x + chart.row_gutter.left)))
+                        .set("y", options.round(y + chart.row_height / 2.0)),
+                );
+            }
         }
+
         // last row
         {
-            let y = chart.gutter.top + (chart.rows.len() as f32 * chart.row_height);
+            let y = rows_bottom;
             rows_g.append(
                 Line::new()
-                    .set("class", "outer-lines")
-                    .set("x1", x1)
-                    .set("y1", y)
-                    .set("x2", x2)
-                    .set("y2", y),
+                    .set("class", options.class(classes::OUTER_LINES))
+                    .set("x1", options.round(mirror_x(x1)))
+                    .set("y1", options.round(y))
+                    .set("x2", options.round(mirror_x(x2)))
+                    .set("y2", options.round(y)),
             );
         }
 
-        doc.append(rows_g);
+        chart_g.append(rows_g);
+
+        // An elbow from the end of each predecessor to the start of the item that follows it
+        if options.arrows {
+            let mut arrows_g = layer("dependencies", "Dependencies");
+            let row_mid_y = |index: usize| row_ys[index] + chart.rows[index].height / 2.0;
+            let row_end_x = |row: &RowRenderData| row.offset + row.length.unwrap_or(0.0);
+
+            for (to_index, to_row) in chart.rows.iter().enumerate() {
+                let Some(from_index) = to_row.predecessor.and_then(|predecessor| {
+                    chart
+                        .rows
+                        .iter()
+                        .position(|row| row.item_index == predecessor)
+                }) else {
+                    continue;
+                };
+                let (from_x, from_y) = (row_end_x(&chart.rows[from_index]), row_mid_y(from_index));
+                let (to_x, to_y) = (to_row.offset, row_mid_y(to_index));
+                let elbow_x = from_x + ARROW_GAP;
+                let mut data = Data::new()
+                    .move_to(options.round_point((mirror_x(from_x), from_y)))
+                    .line_to(options.round_point((mirror_x(elbow_x), from_y)));
+
+                if to_x >= elbow_x + ARROW_GAP {
+                    data = data.line_to(options.round_point((mirror_x(elbow_x), to_y)));
+                } else {
+                    // Go back between the rows when the item starts before its predecessor ends
+                    let between_y = (from_y + to_y) / 2.0;
+
+                    data = data
+                        .line_to(options.round_point((mirror_x(elbow_x), between_y)))
+                        .line_to(options.round_point((mirror_x(to_x - ARROW_GAP), between_y)))
+                        .line_to(options.round_point((mirror_x(to_x - ARROW_GAP), to_y)));
+                }
+
+                arrows_g.append(
+                    Path::new()
+                        .set("class", options.class(classes::DEPENDENCY_ARROW))
+                        .set(
+                            "d",
+                            data.line_to(options.round_point((mirror_x(to_x), to_y))),
+                        ),
+                );
+                arrows_g.append(
+                    Path::new()
+                        .set("class", options.class(classes::DEPENDENCY_ARROW_HEAD))
+                        .set(
+                            "d",
+                            Data::new()
+                                .move_to(options.round_point((mirror_x(to_x), to_y)))
+                                .line_to(options.round_point((mirror_x(to_x - 4.0), to_y - 3.0)))
+                                .line_to(options.round_point((mirror_x(to_x - 4.0), to_y + 3.0)))
+                                .close(),
+                        ),
+                );
+            }
+
+            chart_g.append(arrows_g);
+        }
 
         // Render columns
-        let mut cols_g = Group::new();
-        let y2 = chart.gutter.top + ((chart.rows.len() as f32) * chart.row_height);
+        let mut cols_g = layer("grid", "Grid");
+        let y2 = rows_bottom;
         for (i, col) in chart.cols.iter().enumerate() {
             let line_x = chart.gutter.left
                 + chart.title_width
@@ -587,106 +4493,494 @@ impl<'a> GanttChartTool<'a> {
 
             cols_g.append(
                 Text::new(&col.month_name)
-                    .set("class", "heading")
-                    .set("x", line_x + chart.max_month_width / 2.0)
-                    .set("y", name_y),
+                    .set("class", options.class(classes::HEADING))
+                    .set("x", options.round(mirror_x(line_x + col.width / 2.0)))
+                    .set("y", options.round(name_y)),
             );
 
+            // The first line is the title divider when there is a title column
+            if i == 0 && chart.has_title_column() && !options.title_divider {
+                continue;
+            }
+
             cols_g.append(
                 Line::new()
-                    .set("class", "inner-lines")
-                    .set("x1", line_x)
-                    .set("y1", chart.gutter.top)
-                    .set("x2", line_x)
-                    .set("y2", y2),
+                    .set("class", options.class(classes::INNER_LINES))
+                    .set("x1", options.round(mirror_x(line_x)))
+                    .set("y1", options.round(chart.gutter.top))
+                    .set("x2", options.round(mirror_x(line_x)))
+                    .set("y2", options.round(y2)),
             );
         }
         // last line
-        {
-            let x = chart.gutter.left + chart.title_width;
+        if chart.has_title_column() && options.title_divider {
+            let x = mirror_x(chart.gutter.left + chart.title_width);
             cols_g.append(
                 Line::new()
-                    .set("class", "inner-lines")
-                    .set("x1", x)
-                    .set("y1", chart.gutter.top)
-                    .set("x2", x)
-                    .set("y2", y2),
+                    .set("class", options.class(classes::INNER_LINES))
+                    .set("x1", options.round(x))
+                    .set("y1", options.round(chart.gutter.top))
+                    .set("x2", options.round(x))
+                    .set("y2", options.round(y2)),
             );
         }
 
-        doc.append(cols_g);
+        chart_g.append(cols_g);
 
         // "Tasks" header
-        {
-            let x = chart.gutter.left + chart.row_gutter.left;
+        if chart.has_title_column() && options.task_header {
+            let x = mirror_x(chart.gutter.left + chart.row_gutter.left);
             let y = chart.gutter.top - chart.row_gutter.bottom - chart.row_height / 2.0;
-            doc.append(
+            chart_g.append(
                 Text::new("Tasks")
-                    .set("class", "heading task-heading")
-                    .set("x", x)
-                    .set("y", y),
+                    .set(
+                        "class",
+                        options.classes(&[classes::HEADING, classes::TASK_HEADING]),
+                    )
+                    .set("x", options.round(x))
+                    .set("y", options.round(y)),
             );
         }
 
-        // Chart title
+        // Chart title and subtitle, above the column headings
         {
-            doc.append(
+            let heading_y = chart.gutter.top - chart.row_gutter.bottom - chart.row_height;
+            let phase_y = heading_y - chart.phase_height;
+            let title_y = if chart.subtitle.is_some() {
+                phase_y - 40.0
+            } else {
+                phase_y - 20.0
+            };
+            let mut title_g = layer("title", "Title");
+
+            if !chart.phases.is_empty() {
+                let mut phases_g = layer("phases", "Phases");
+
+                for (i, phase) in chart.phases.iter().enumerate() {
+                    phases_g.append(
+                        Rectangle::new()
+                            .set("class", options.class(&classes::phase(i)))
+                            .set(
+                                "x",
+                                options.round(mirror_rect_x(phase.offset, phase.length)),
+                            )
+                            .set("y", options.round(phase_y + chart.row_gutter.top))
+                            .set("rx", options.round(chart.rect_corner_radius))
+                            .set("ry", options.round(chart.rect_corner_radius))
+                            .set("width", options.round(phase.length))
+                            .set(
+                                "height",
+                                options.round(chart.phase_height - chart.row_gutter.height()),
+                            ),
+                    );
+                    phases_g.append(
+                        Text::new(&phase.name)
+                            .set("class", options.class(classes::PHASE_LABEL))
+                            .set(
+                                "x",
+                                options.round(mirror_x(phase.offset + phase.length / 2.0)),
+                            )
+                            .set("y", options.round(phase_y + chart.phase_height / 2.0)),
+                    );
+                }
+
+                chart_g.append(phases_g);
+            }
+            // The alignment is across the whole document, whichever way it reads
+            let title_x = match options.title_align {
+                None => mirror_x(chart.gutter.left),
+                Some(TitleAlign::Left) => chart.gutter.left,
+                Some(TitleAlign::Center) => width / 2.0,
+                Some(TitleAlign::Right) => width - chart.gutter.right,
+            };
+
+            title_g.append(
                 Text::new(&chart.title)
-                    .set("class", "title")
-                    .set("x", chart.gutter.left)
-                    .set("y", 25.0),
+                    .set("class", options.class(classes::TITLE))
+                    .set("x", options.round(title_x))
+                    .set("y", options.round(title_y)),
             );
+
+            if let Some(ref subtitle) = chart.subtitle {
+                title_g.append(
+                    Text::new(subtitle)
+                        .set("class", options.class(classes::SUBTITLE))
+                        .set("x", options.round(title_x))
+                        .set("y", options.round(title_y + 22.0)),
+                );
+            }
+
+            if options.show_duration {
+                title_g.append(
+                    Text::new(format!(
+                        "{} calendar days, {} working days",
+                        chart.calendar_days, chart.working_days
+                    ))
+                    .set("class", options.class(classes::DURATION))
+                    .set("x", options.round(mirror_x(width - chart.gutter.right)))
+                    .set("y", options.round(title_y)),
+                );
+            }
+
+            // A compact legend shares the title line, at the other end from the title
+            if options.legend == LegendPosition::Top {
+                let mut legend_g = layer("legend", "Legend").set("role", "list");
+                let swatch_width = 10.0;
+                let labels: Vec<String> = chart
+                    .resources
+                    .iter()
+                    .map(|resource| {
+                        if options.legend_stats {
+                            format!(
+                                "{} ({} tasks, {} days)",
+                                resource.name, resource.num_tasks, resource.num_days
+                            )
+                        } else {
+                            resource.name.clone()
+                        }
+                    })
+                    .collect();
+                let entry_width = |label: &str| {
+                    swatch_width + 4.0 + label.chars().count() as f32 * NOTE_CHAR_WIDTH
+                };
+                let legend_width = labels
+                    .iter()
+                    .map(|label| entry_width(label) + 12.0)
+                    .sum::<f32>();
+                // The title goes at the end of the line when it's aligned against the reading direction
+                let title_at_end = match options.title_align {
+                    Some(TitleAlign::Left) => options.rtl,
+                    Some(TitleAlign::Right) => !options.rtl,
+                    _ => false,
+                };
+                let mut entry_x = if title_at_end {
+                    chart.gutter.left
+                } else {
+                    // Leave room for the durations, which go at the end
+                    let duration_width = if options.show_duration {
+                        format!(
+                            "{} calendar days, {} working days",
+                            chart.calendar_days, chart.working_days
+                        )
+                        .chars()
+                        .count() as f32
+                            * NOTE_CHAR_WIDTH
+                            + 12.0
+                    } else {
+                        0.0
+                    };
+
+                    width - chart.gutter.right - duration_width - legend_width + 12.0
+                };
+                let y = title_y - 6.0;
+
+                for (i, label) in labels.iter().enumerate() {
+                    let mut entry_g = Group::new().set("role", "listitem");
+
+                    entry_g.append(
+                        Rectangle::new()
+                            .set(
+                                "class",
+                                options.class(&classes::bar(&classes::resource(i), false)),
+                            )
+                            .set("x", options.round(mirror_rect_x(entry_x, swatch_width)))
+                            .set("y", options.round(y - swatch_width / 2.0))
+                            .set("rx", options.round(chart.rect_corner_radius))
+                            .set("ry", options.round(chart.rect_corner_radius))
+                            .set("width", options.round(swatch_width))
+                            .set("height", options.round(swatch_width)),
+                    );
+                    entry_g.append(
+                        Text::new(label.as_str())
+                            .set("class", options.class(classes::LEGEND_ENTRY))
+                            .set("x", options.round(mirror_x(entry_x + swatch_width + 4.0)))
+                            .set("y", options.round(y)),
+                    );
+
+                    legend_g.append(entry_g);
+                    entry_x += entry_width(label) + 12.0;
+                }
+
+                chart_g.append(legend_g);
+            }
+
+            chart_g.append(title_g);
         }
 
         // Date marker
         {
-            if let Some(offset) = chart.marked_date_offset {
+            if let (Some(offset), Some(date)) = (chart.marked_date_offset, chart.marked_date) {
                 let y1 = chart.gutter.top - 5.0;
-                let y2 = chart.gutter.top + ((chart.rows.len() as f32) * chart.row_height) + 5.0;
-                doc.append(
+                let y2 = rows_bottom + 5.0;
+                let mut marker_g = layer("marker", "Marker").add(
                     Line::new()
-                        .set("class", "marker")
-                        .set("x1", offset)
-                        .set("y1", y1)
-                        .set("x2", offset)
-                        .set("y2", y2),
+                        .set("class", options.class(classes::MARKER))
+                        .set("x1", options.round(mirror_x(offset)))
+                        .set("y1", options.round(y1))
+                        .set("x2", options.round(mirror_x(offset)))
+                        .set("y2", options.round(y2))
+                        .add(Title::new(chart.format_date(date))),
+                );
+
+                // The label hangs beside the top of the line
+                if let Some(ref label) = chart.marked_date_label {
+                    marker_g.append(
+                        Text::new(label.as_str())
+                            .set("class", options.class(classes::MARKER_LABEL))
+                            .set("x", options.round(mirror_x(offset + 4.0)))
+                            .set("y", options.round(chart.gutter.top + 2.0)),
+                    );
+                }
+
+                chart_g.append(marker_g);
+            }
+        }
+
+        // Project start and end
+        if options.show_bounds {
+            let mut bounds_g = layer("bounds", "Bounds");
+            let (start_offset, end_offset) = chart.project_offsets;
+
+            for (offset, date) in [
+                (start_offset, chart.project_start_date),
+                (end_offset, chart.project_end_date),
+            ] {
+                bounds_g.append(
+                    Text::new(chart.format_date(date))
+                        .set("class", options.class(classes::AXIS_DATE))
+                        .set("x", options.round(mirror_x(offset)))
+                        .set("y", options.round(rows_bottom + 4.0)),
+                );
+            }
+
+            chart_g.append(bounds_g);
+        }
+
+        // Utilization, stacked up from the bottom of its band in the resource order
+        if max_utilization > 0 {
+            let mut utilization_g = layer("utilization", "Utilization");
+            let bottom_y =
+                rows_bottom + bounds_height + utilization_height - chart.row_gutter.bottom;
+
+            if chart.has_title_column() {
+                utilization_g.append(
+                    Text::new("Utilization")
+                        .set("class", options.class(classes::ITEM))
+                        .set(
+                            "x",
+                            options.round(mirror_x(chart.gutter.left + chart.row_gutter.left)),
+                        )
+                        .set(
+                            "y",
+                            options.round(rows_bottom + bounds_height + utilization_height / 2.0),
+                        ),
                 );
             }
+
+            for day in chart.utilization.iter() {
+                let mut y = bottom_y;
+
+                for (i, count) in day.counts.iter().enumerate() {
+                    if *count == 0 {
+                        continue;
+                    }
+
+                    let step_height = *count as f32 * UTILIZATION_STEP;
+
+                    y -= step_height;
+                    utilization_g.append(
+                        Rectangle::new()
+                            .set(
+                                "class",
+                                options.class(&classes::bar(&classes::resource(i), false)),
+                            )
+                            .set("x", options.round(mirror_rect_x(day.offset, day.width)))
+                            .set("y", options.round(y))
+                            .set("width", options.round(day.width))
+                            .set("height", options.round(step_height)),
+                    );
+                }
+            }
+
+            chart_g.append(utilization_g);
         }
 
         // Legend
-        if use_legend {
-            let mut legend_g = Group::new();
-            for (i, res) in chart.resources.iter().enumerate() {
-                let y = chart.gutter.top + ((chart.rows.len() as f32) * chart.row_height);
+        if options.legend == LegendPosition::Bottom {
+            let mut legend_g = layer("legend", "Legend").set("role", "list");
+            // Each entry is as wide as its label is estimated to be, plus the swatch
+            let mut entry_x = chart.resource_gutter.left;
+            for (i, resource) in chart.resources.iter().enumerate() {
+                let y = rows_bottom + bounds_height + utilization_height;
                 let block_width = chart.resource_height - chart.resource_gutter.height();
+                let mut entry_g = Group::new().set("role", "listitem");
 
-                let res_x = chart.resource_gutter.left + ((i + 1) as f32) * 100.0 - 5.0;
+                let label = if options.legend_stats {
+                    format!(
+                        "{} ({} tasks, {} days)",
+                        resource.name, resource.num_tasks, resource.num_days
+                    )
+                } else {
+                    resource.name.clone()
+                };
+                let res_x = entry_x + label.chars().count() as f32 * ITEM_CHAR_WIDTH;
                 let res_y = y + chart.resource_height / 2.0;
-                legend_g.append(
-                    Text::new(res)
-                        .set("class", "resource")
-                        .set("x", res_x)
-                        .set("y", res_y),
+                entry_g.append(
+                    Text::new(label)
+                        .set("class", options.class(classes::RESOURCE))
+                        .set("x", options.round(mirror_x(res_x)))
+                        .set("y", options.round(res_y)),
                 );
 
-                let rect_x = chart.resource_gutter.left + ((i + 1) as f32) * 100.0 + 5.0;
+                let rect_x = res_x + 10.0;
                 let rect_y = y + chart.resource_gutter.top;
-                legend_g.append(
+
+                entry_x = rect_x + block_width + 2.0 * chart.resource_gutter.right;
+                entry_g.append(
                     Rectangle::new()
-                        .set("class", format!("resource-{}-closed", i))
-                        .set("x", rect_x)
-                        .set("y", rect_y)
-                        .set("rx", chart.rect_corner_radius)
-                        .set("ry", chart.rect_corner_radius)
-                        .set("width", block_width)
-                        .set("height", block_width),
+                        .set(
+                            "class",
+                            options.class(&classes::bar(&classes::resource(i), false)),
+                        )
+                        .set("x", options.round(mirror_rect_x(rect_x, block_width)))
+                        .set("y", options.round(rect_y))
+                        .set("rx", options.round(chart.rect_corner_radius))
+                        .set("ry", options.round(chart.rect_corner_radius))
+                        .set("width", options.round(block_width))
+                        .set("height", options.round(block_width)),
                 );
+
+                // A picture of the resource goes after its swatch
+                let icon_x = rect_x + block_width + chart.resource_gutter.right;
+
+                if let Some(ref icon) = resource.icon {
+                    entry_g.append(
+                        Image::new()
+                            .set("class", options.class(classes::RESOURCE_ICON))
+                            .set("href", icon.as_str())
+                            .set("x", options.round(mirror_rect_x(icon_x, block_width)))
+                            .set("y", options.round(rect_y))
+                            .set("width", options.round(block_width))
+                            .set("height", options.round(block_width)),
+                    );
+                } else if let Some(ref initials) = resource.initials {
+                    let cx = mirror_x(icon_x + block_width / 2.0);
+                    let cy = rect_y + block_width / 2.0;
+
+                    entry_g.append(
+                        Circle::new()
+                            .set(
+                                "class",
+                                options.class(&classes::bar(&classes::resource(i), false)),
+                            )
+                            .set("cx", options.round(cx))
+                            .set("cy", options.round(cy))
+                            .set("r", options.round(block_width / 2.0)),
+                    );
+                    entry_g.append(
+                        Text::new(initials.as_str())
+                            .set("class", options.class(classes::RESOURCE_INITIALS))
+                            .set("x", options.round(cx))
+                            .set("y", options.round(cy)),
+                    );
+                }
+
+                if resource.icon.is_some() || resource.initials.is_some() {
+                    entry_x += block_width + chart.resource_gutter.right;
+                }
+
+                legend_g.append(entry_g);
             }
 
-            doc.append(legend_g);
+            chart_g.append(legend_g);
+        }
+
+        // Scaled as the document would be, with a transform that can be changed to put the chart in place
+        if options.fragment {
+            let fragment_g = chart_g.set(
+                "transform",
+                format!("translate(0, 0) scale({})", options.scale),
+            );
+
+            return Ok(fragment_g.to_string());
         }
 
-        Ok(doc.to_string())
+        let document = if let Some(page) = options.page {
+            // The page is in millimeters, with the chart scaled to fit inside the margins
+            let (page_width, page_height) = page.size(options.landscape);
+            let page_scale = ((page_width - 2.0 * PAGE_MARGIN) / width)
+                .min((page_height - 2.0 * PAGE_MARGIN) / height);
+            let (x, y) = if options.page_center {
+                (
+                    (page_width - width * page_scale) / 2.0,
+                    (page_height - height * page_scale) / 2.0,
+                )
+            } else {
+                (PAGE_MARGIN, PAGE_MARGIN)
+            };
+
+            Document::new()
+                .set("width", format!("{}mm", page_width))
+                .set("height", format!("{}mm", page_height))
+                .set("viewBox", (0, 0, page_width, page_height))
+                .set("role", "img")
+                .set("aria-label", chart.title.as_str())
+                .add(
+                    Rectangle::new()
+                        .set("class", options.class(classes::BACKGROUND))
+                        .set("width", options.round(page_width))
+                        .set("height", options.round(page_height)),
+                )
+                .add(chart_g.set(
+                    "transform",
+                    format!(
+                        "translate({}, {}) scale({})",
+                        options.round(x),
+                        options.round(y),
+                        page_scale
+                    ),
+                ))
+        } else {
+            // A capped height shows a window of the chart, moved down so the scrolled-to row is where the first row would be
+            let (view_y, view_height) = match options.max_height {
+                Some(max_height) if height * options.scale > max_height => {
+                    if options.scroll_to >= chart.rows.len() {
+                        bail!(
+                            "Cannot scroll to row {}, there are only {} rows",
+                            options.scroll_to,
+                            chart.rows.len()
+                        );
+                    }
+
+                    let view_height = max_height / options.scale;
+                    let view_y = (row_ys[options.scroll_to] - chart.gutter.top)
+                        .min(height - view_height)
+                        .max(0.0);
+
+                    (view_y, view_height)
+                }
+                _ => (0.0, height),
+            };
+
+            // Only the document size is scaled, the content is stretched to fit by the view box
+            Document::new()
+                .set("width", options.round(width * options.scale))
+                .set("height", options.round(view_height * options.scale))
+                .set(
+                    "viewBox",
+                    (
+                        0.0,
+                        options.round(view_y),
+                        options.round(width),
+                        options.round(view_height),
+                    ),
+                )
+                .set("style", "background-color: white;")
+                .set("role", "img")
+                .set("aria-label", chart.title.as_str())
+                .add(chart_g)
+        };
+
+        Ok(document.to_string())
     }
 }
@@ -8,6 +8,15 @@ macro_rules! output {
   };
 }
 #[macro_export]
+macro_rules! debug {
+  ($log: expr, $fmt: expr) => {
+    $log.debug(format_args!($fmt))
+  };
+  ($log: expr, $fmt: expr, $($args: tt)+) => {
+    $log.debug(format_args!($fmt, $($args)+))
+  };
+}
+#[macro_export]
 macro_rules! warning {
   ($log: expr, $fmt: expr) => {
     $log.warning(format_args!($fmt))
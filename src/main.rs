@@ -19,6 +19,9 @@ impl GanttChartLog for GanttChartLogger {
     fn output(&self, args: Arguments) {
         println!("{}", args);
     }
+    fn debug(&self, args: Arguments) {
+        eprintln!("{}", args);
+    }
     fn warning(&self, args: Arguments) {
         eprintln!("{}", format!("warning: {}", args).yellow());
     }
@@ -1 +1,3 @@
+pub mod classes;
+pub mod colors;
 pub mod shapes;
@@ -0,0 +1,79 @@
+// All the style class names used in the chart, so that they can be prefixed consistently
+pub static CHART: &str = "chart";
+pub static OUTER_LINES: &str = "outer-lines";
+pub static INNER_LINES: &str = "inner-lines";
+pub static ITEM: &str = "item";
+pub static RESOURCE: &str = "resource";
+pub static RESOURCE_ICON: &str = "resource-icon";
+pub static RESOURCE_INITIALS: &str = "resource-initials";
+pub static LEGEND_ENTRY: &str = "legend-entry";
+pub static TITLE: &str = "title";
+pub static SUBTITLE: &str = "subtitle";
+pub static HEADING: &str = "heading";
+pub static TASK_HEADING: &str = "task-heading";
+pub static MILESTONE: &str = "milestone";
+pub static MARKER: &str = "marker";
+pub static MARKER_LABEL: &str = "marker-label";
+pub static ROW_STRIPE: &str = "row-stripe";
+pub static WEEKEND_BAND: &str = "weekend-band";
+pub static WEEKEND_MARK: &str = "weekend-mark";
+pub static BAR_LABEL: &str = "bar-label";
+pub static CRITICAL: &str = "critical";
+pub static OVERDUE: &str = "overdue";
+pub static BACKGROUND: &str = "background";
+pub static WEEK_LINE: &str = "week-line";
+pub static WEEK_LABEL: &str = "week-label";
+pub static AXIS_DATE: &str = "axis-date";
+pub static PHASE_LABEL: &str = "phase-label";
+pub static NOTE: &str = "note";
+pub static TASK_GUIDE: &str = "task-guide";
+pub static SUMMARY: &str = "summary";
+pub static SUMMARY_TRACK: &str = "summary-track";
+pub static OVERFLOW: &str = "overflow";
+pub static OVERFLOW_ARROW: &str = "overflow-arrow";
+pub static BASELINE: &str = "baseline";
+pub static SLACK: &str = "slack";
+pub static DURATION: &str = "duration";
+pub static STATUS: &str = "status";
+pub static DEPENDENCY_ARROW: &str = "dependency-arrow";
+pub static DEPENDENCY_ARROW_HEAD: &str = "dependency-arrow-head";
+
+/// The bar colors for a resource
+pub fn resource(index: usize) -> String {
+    format!("resource-{index}")
+}
+
+/// The bar colors for an item with its own color
+pub fn item(index: usize) -> String {
+    format!("item-{index}")
+}
+
+/// An open or closed bar in the given colors
+pub fn bar(colors: &str, open: bool) -> String {
+    format!("{colors}-{}", if open { "open" } else { "closed" })
+}
+
+/// The milestone color for a resource
+pub fn milestone(index: usize) -> String {
+    format!("milestone-{index}")
+}
+
+/// The id of the bar fill gradient for a resource
+pub fn gradient(index: usize) -> String {
+    format!("gradient-{index}")
+}
+
+/// The band across the dates of a group of items
+pub fn phase(index: usize) -> String {
+    format!("phase-{index}")
+}
+
+/// The marker color for an item status
+pub fn status(status: &str) -> String {
+    format!("status-{status}")
+}
+
+/// The background tint behind the rows of a resource
+pub fn swimlane(index: usize) -> String {
+    format!("swimlane-{index}")
+}
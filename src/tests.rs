@@ -0,0 +1,2965 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+use super::*;
+
+// Keeps everything logged, so that the tests can look at it
+#[derive(Default)]
+struct TestLogger {
+    output: RefCell<Vec<String>>,
+    debug: RefCell<Vec<String>>,
+    warnings: RefCell<Vec<String>>,
+    errors: RefCell<Vec<String>>,
+}
+
+impl GanttChartLog for TestLogger {
+    fn output(&self, args: Arguments) {
+        self.output.borrow_mut().push(args.to_string());
+    }
+    fn debug(&self, args: Arguments) {
+        self.debug.borrow_mut().push(args.to_string());
+    }
+    fn warning(&self, args: Arguments) {
+        self.warnings.borrow_mut().push(args.to_string());
+    }
+    fn error(&self, args: Arguments) {
+        self.errors.borrow_mut().push(args.to_string());
+    }
+}
+
+// Two resources, with a task following on from the first and a task starting on a Monday
+static CHART: &str = r#"{
+  title: "Test Chart",
+  resources: ["Alice", "Bob"],
+  items: [
+    { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+    { title: "Second", duration: 3 },
+    { title: "Third", startDate: "2022-01-10", duration: 1, resource: 1 },
+  ],
+}"#;
+
+// Each test writes its own file, as the tests run in parallel
+fn chart_file(content: &str, extension: &str) -> PathBuf {
+    static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "gantt-test-{}-{}.{extension}",
+        std::process::id(),
+        NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn run(log: &TestLogger, chart: &str, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let path = chart_file(chart, "json5");
+    let result = GanttChartTool::new(log).run_to_string(
+        ["gantt", path.to_str().unwrap()]
+            .iter()
+            .chain(args.iter())
+            .map(Into::into),
+    );
+
+    std::fs::remove_file(path).unwrap();
+    result
+}
+
+#[test]
+fn json_format_has_item_end_dates() {
+    let output = run(&TestLogger::default(), CHART, &["--format", "json"]).unwrap();
+    let schedule: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let end_dates: Vec<&str> = schedule["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["endDate"].as_str().unwrap())
+        .collect();
+
+    // The second item ends on a Saturday, so it is moved on to the Monday
+    assert_eq!(end_dates, ["2022-01-05", "2022-01-10", "2022-01-11"]);
+}
+
+fn layout(chart: &str, args: &[&str]) -> RenderData {
+    let cli = Cli::try_parse_from(["gantt"].iter().chain(args.iter())).unwrap();
+
+    GanttChartTool::new(&TestLogger::default())
+        .process_chart_data(
+            &cli.layout_options().unwrap(),
+            &json5::from_str(chart).unwrap(),
+        )
+        .unwrap()
+}
+
+#[test]
+fn chart_start_adds_month_column() {
+    let pinned_chart = CHART.replacen('{', r#"{ chartStart: "2021-12-20","#, 1);
+    let months = |chart: &RenderData| -> Vec<String> {
+        chart
+            .cols
+            .iter()
+            .map(|col| col.month_name.clone())
+            .collect()
+    };
+
+    assert_eq!(months(&layout(CHART, &[])), ["Jan"]);
+    assert_eq!(months(&layout(&pinned_chart, &[])), ["Dec", "Jan"]);
+}
+
+// A task a day for one resource, as many as needed
+fn tasks_chart(num_tasks: usize) -> String {
+    let items: Vec<String> = (0..num_tasks)
+        .map(|i| match i {
+            0 => r#"{ title: "Task 0", startDate: "2022-01-03", duration: 1, resource: 0 }"#
+                .to_string(),
+            _ => format!(r#"{{ title: "Task {i}", duration: 1 }}"#),
+        })
+        .collect();
+
+    format!(
+        r#"{{ title: "Tasks", resources: ["Alice"], items: [{}] }}"#,
+        items.join(", ")
+    )
+}
+
+// The attributes of each element with the tag, which is as much as the tests need of the SVG
+fn elements<'a>(svg: &'a str, tag: &str) -> Vec<HashMap<&'a str, &'a str>> {
+    let open = format!("<{tag}");
+
+    svg.match_indices(&open)
+        .filter_map(|(i, _)| {
+            let rest = &svg[i + open.len()..];
+
+            if !rest.starts_with([' ', '>', '/']) {
+                return None;
+            }
+
+            let mut attributes = HashMap::new();
+            let mut rest = &rest[..rest.find('>').unwrap()];
+
+            while let Some(equals) = rest.find("=\"") {
+                let value = &rest[equals + 2..];
+                let end = value.find('"').unwrap();
+
+                attributes.insert(rest[..equals].trim(), &value[..end]);
+                rest = &value[end + 1..];
+            }
+
+            Some(attributes)
+        })
+        .collect()
+}
+
+// The words in each text element, without any of the markup inside it
+fn texts(svg: &str) -> Vec<String> {
+    svg.split("<text")
+        .skip(1)
+        .map(|text| {
+            let content = &text[text.find('>').unwrap() + 1..text.find("</text>").unwrap()];
+            let mut words = String::new();
+            let mut in_tag = false;
+
+            for c in content.chars() {
+                match c {
+                    '<' => in_tag = true,
+                    '>' => in_tag = false,
+                    _ if !in_tag => words.push(c),
+                    _ => (),
+                }
+            }
+
+            words.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .collect()
+}
+
+fn with_class<'a>(svg: &'a str, tag: &str, class: &str) -> Vec<HashMap<&'a str, &'a str>> {
+    elements(svg, tag)
+        .into_iter()
+        .filter(|element| {
+            element
+                .get("class")
+                .is_some_and(|classes| classes.split_whitespace().any(|name| name == class))
+        })
+        .collect()
+}
+
+#[test]
+fn zebra_stripes_every_other_row() {
+    let log = TestLogger::default();
+
+    for (num_rows, num_stripes) in [(2, 1), (5, 2), (6, 3)] {
+        let svg = run(&log, &tasks_chart(num_rows), &["--zebra"]).unwrap();
+
+        assert_eq!(with_class(&svg, "rect", "row-stripe").len(), num_stripes);
+        // Behind the bars
+        assert!(svg.find("class=\"row-stripe\"") < svg.find("class=\"resource-0-closed\""));
+    }
+
+    let svg = run(&log, &tasks_chart(5), &[]).unwrap();
+
+    assert!(with_class(&svg, "rect", "row-stripe").is_empty());
+}
+
+#[test]
+fn weekend_bands_fall_on_weekends() {
+    let svg = run(&TestLogger::default(), CHART, &["--weekend-bands"]).unwrap();
+    let bands = with_class(&svg, "rect", "weekend-band");
+    let day_width = 200.0 / 31.0;
+    // The chart starts at the title column, on Saturday the 1st of January 2022
+    let band_xs: Vec<f32> = bands
+        .iter()
+        .map(|band| (band["x"].parse::<f32>().unwrap() - 220.0) / day_width)
+        .collect();
+
+    // Both weekends within the two weeks from Monday the 3rd, and the rest of the month
+    assert_eq!(
+        band_xs.iter().filter(|x| (2.0..16.0).contains(*x)).count(),
+        4
+    );
+    assert_eq!(bands.len(), 10);
+
+    for (band_x, day) in band_xs.iter().zip([0, 1, 7, 8, 14, 15, 21, 22, 28, 29]) {
+        assert!((band_x - day as f32).abs() < 0.01);
+    }
+
+    for band in bands.iter() {
+        assert!((band["width"].parse::<f32>().unwrap() - day_width).abs() < 0.01);
+    }
+}
+
+#[test]
+fn scale_sizes_document_but_not_view_box() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &[]).unwrap();
+    let scaled_svg = run(&log, CHART, &["--scale", "2.0"]).unwrap();
+    let (document, scaled_document) = (&elements(&svg, "svg")[0], &elements(&scaled_svg, "svg")[0]);
+    let size =
+        |document: &HashMap<&str, &str>, name: &str| -> f32 { document[name].parse().unwrap() };
+
+    assert_eq!(
+        size(scaled_document, "width"),
+        2.0 * size(document, "width")
+    );
+    assert_eq!(
+        size(scaled_document, "height"),
+        2.0 * size(document, "height")
+    );
+    assert_eq!(scaled_document["viewBox"], document["viewBox"]);
+    assert_eq!(
+        document["viewBox"],
+        format!("0 0 {} {}", document["width"], document["height"])
+    );
+    assert!(run(&log, CHART, &["--scale", "0"]).is_err());
+}
+
+#[test]
+fn early_and_duplicate_start_dates_warn() {
+    let log = TestLogger::default();
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 5, resource: 0 },
+        { title: "Early", startDate: "2022-01-05", duration: 1 },
+        { title: "Same", startDate: "2022-01-05", duration: 1 },
+      ],
+    }"#;
+
+    run(&log, chart, &[]).unwrap();
+
+    let warnings = log.warnings.borrow();
+
+    assert!(warnings.contains(
+        &"Item 'Early' starts on 2022-01-05 before the previous item 'First' ends on 2022-01-10"
+            .to_string()
+    ));
+    assert!(warnings
+        .contains(&"Items 'Early' and 'Same' have the same start date 2022-01-05".to_string()));
+
+    let log = TestLogger::default();
+
+    run(&log, CHART, &[]).unwrap();
+
+    assert!(log.warnings.borrow().is_empty());
+}
+
+#[test]
+fn max_width_caps_document_width() {
+    let log = TestLogger::default();
+    let chart = CHART.replace("2022-01-10", "2022-06-10");
+    let width = |svg: &str| -> f32 { elements(svg, "svg")[0]["width"].parse().unwrap() };
+
+    assert!(width(&run(&log, &chart, &[]).unwrap()) > 1000.0);
+    assert!((width(&run(&log, &chart, &["--max-width", "600"]).unwrap()) - 600.0).abs() < 0.01);
+    // Nothing changes when the chart already fits
+    assert_eq!(
+        width(&run(&log, &chart, &["--max-width", "5000"]).unwrap()),
+        width(&run(&log, &chart, &[]).unwrap())
+    );
+}
+
+// The declarations of the style rule for a selector
+fn style_rule<'a>(svg: &'a str, selector: &str) -> Option<&'a str> {
+    let start = svg.find(&format!("{selector}{{"))? + selector.len() + 1;
+
+    Some(&svg[start..start + svg[start..].find('}')?])
+}
+
+// The value of one declaration in a style rule
+fn style_property<'a>(svg: &'a str, selector: &str, property: &str) -> Option<&'a str> {
+    style_rule(svg, selector)?
+        .split(';')
+        .find_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+
+            (name.trim() == property).then(|| value.trim())
+        })
+}
+
+#[test]
+fn milestone_takes_resource_color() {
+    let chart = CHART.replace(
+        r#"duration: 1, resource: 1 },"#,
+        r#"duration: 1, resource: 1 }, { title: "Done", resource: 1 },"#,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+    let milestone = &with_class(&svg, "path", "milestone")[0];
+
+    assert_eq!(milestone["class"], "milestone milestone-1");
+    assert_eq!(
+        style_property(&svg, ".milestone-1", "fill"),
+        style_property(&svg, ".resource-1-closed", "fill")
+    );
+    assert_ne!(
+        style_property(&svg, ".milestone-1", "fill"),
+        style_property(&svg, ".resource-0-closed", "fill")
+    );
+}
+
+// The x of every vertical line
+fn vertical_line_xs(svg: &str) -> Vec<f32> {
+    elements(svg, "line")
+        .iter()
+        .filter(|line| line["x1"] == line["x2"])
+        .map(|line| line["x1"].parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn inline_titles_drop_title_column() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &[]).unwrap();
+    let inline_svg = run(&log, CHART, &["--inline-titles"]).unwrap();
+    let width = |svg: &str| -> f32 { elements(svg, "svg")[0]["width"].parse().unwrap() };
+
+    // The title column divider is at the margin plus the title width
+    assert!(vertical_line_xs(&svg).contains(&220.0));
+    assert!(!vertical_line_xs(&inline_svg).contains(&220.0));
+    assert_eq!(width(&svg) - width(&inline_svg), 210.0);
+    assert!(texts(&svg).contains(&"Tasks".to_string()));
+    assert!(!texts(&inline_svg).contains(&"Tasks".to_string()));
+
+    // The titles start at the bars instead
+    let bars = with_class(&inline_svg, "rect", "resource-0-closed");
+    let labels = with_class(&inline_svg, "text", "bar-label");
+
+    assert_eq!(labels.len(), 3);
+    assert_eq!(
+        labels[0]["x"].parse::<f32>().unwrap(),
+        bars[0]["x"].parse::<f32>().unwrap() + 5.0
+    );
+}
+
+#[test]
+fn critical_path_follows_chain_to_end() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "A", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "B", duration: 3 },
+        { title: "C", startDate: "2022-01-03", duration: 1, resource: 1 },
+        { title: "D", duration: 1 },
+      ],
+    }"#;
+    let critical: Vec<bool> = layout(chart, &[])
+        .rows
+        .iter()
+        .map(|row| row.critical)
+        .collect();
+
+    assert_eq!(critical, [true, true, false, false]);
+
+    let log = TestLogger::default();
+    let svg = run(&log, chart, &["--critical-path"]).unwrap();
+
+    assert_eq!(with_class(&svg, "rect", "critical").len(), 2);
+    assert!(with_class(&run(&log, chart, &[]).unwrap(), "rect", "critical").is_empty());
+}
+
+#[test]
+fn item_color_overrides_resource_color() {
+    let chart = CHART.replace(
+        r#"{ title: "Second", duration: 3 }"#,
+        r##"{ title: "Second", duration: 3, color: "#00ff00" }, { title: "Open", duration: 1, color: "#00ff00", open: true }"##,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+
+    assert_eq!(with_class(&svg, "rect", "item-1-closed").len(), 1);
+    assert_eq!(
+        style_property(&svg, ".item-1-closed", "fill"),
+        Some("#00ff00")
+    );
+    assert_ne!(
+        style_property(&svg, ".resource-0-closed", "fill"),
+        Some("#00ff00")
+    );
+    // An open bar is still only outlined
+    assert_eq!(with_class(&svg, "rect", "item-2-open").len(), 1);
+    assert_eq!(
+        style_property(&svg, ".item-2-open", "stroke"),
+        Some("#00ff00")
+    );
+    assert_eq!(style_property(&svg, ".item-2-open", "fill"), Some("none"));
+}
+
+#[test]
+fn document_describes_chart() {
+    let chart = CHART.replace(
+        r#"duration: 1, resource: 1 },"#,
+        r#"duration: 1, resource: 1 }, { title: "Done" },"#,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+    let document = &elements(&svg, "svg")[0];
+    let desc = &svg[svg.find("<desc>").unwrap() + 6..svg.find("</desc>").unwrap()];
+
+    assert_eq!(document["role"], "img");
+    assert_eq!(document["aria-label"], "Test Chart");
+    assert_eq!(
+        desc.trim(),
+        "3 tasks and 1 milestones from 2022-01-01 to 2022-01-31"
+    );
+
+    // A list of the rows, and of the resources in the legend
+    for (args, layer, num_items) in [
+        (&[][..], "rows", 4),
+        (&["--legend"][..], "legend", 2),
+        (&["--legend-position", "top"][..], "legend", 2),
+    ] {
+        let svg = run(&TestLogger::default(), &chart, args).unwrap();
+        let groups = elements(&svg, "g");
+        let layer_index = groups
+            .iter()
+            .position(|group| group.get("id") == Some(&layer))
+            .unwrap();
+
+        assert_eq!(groups[layer_index]["role"], "list");
+        assert_eq!(
+            groups[layer_index + 1..]
+                .iter()
+                .take_while(|group| !group.contains_key("id"))
+                .filter(|group| group.get("role") == Some(&"listitem"))
+                .count(),
+            num_items
+        );
+    }
+}
+
+// Starts before the previous item ends, which is only a warning
+static WARNING_CHART: &str = r#"{
+  title: "Test Chart",
+  resources: ["Alice"],
+  items: [
+    { title: "First", startDate: "2022-01-03", duration: 5, resource: 0 },
+    { title: "Early", startDate: "2022-01-05", duration: 1 },
+  ],
+}"#;
+
+#[test]
+fn quiet_withholds_warnings() {
+    let log = TestLogger::default();
+
+    run(&log, WARNING_CHART, &[]).unwrap();
+
+    assert!(!log.warnings.borrow().is_empty());
+
+    let log = TestLogger::default();
+
+    run(&log, WARNING_CHART, &["--quiet"]).unwrap();
+
+    assert!(log.warnings.borrow().is_empty());
+    // Errors still stop the run
+    assert!(run(
+        &log,
+        "{ title: \"Empty\", resources: [], items: [] }",
+        &["--quiet"]
+    )
+    .is_err());
+}
+
+// Answers each request in turn with the next of the responses, on a port of its own
+fn serve(responses: Vec<String>) -> String {
+    use std::{
+        io::BufRead,
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = io::BufReader::new(stream);
+            let mut line = String::new();
+
+            // Read up to the blank line at the end of the request headers
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    format!("http://{address}")
+}
+
+#[test]
+fn input_can_be_fetched_from_url() {
+    let url = serve(vec![
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            CHART.len(),
+            CHART
+        ),
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    ]);
+    let log = TestLogger::default();
+    let svg = GanttChartTool::new(&log)
+        .run_to_string(["gantt".to_string(), format!("{url}/chart.json5")].map(Into::into))
+        .unwrap();
+
+    assert_eq!(elements(&svg, "svg")[0]["aria-label"], "Test Chart");
+
+    let err = GanttChartTool::new(&log)
+        .run_to_string(["gantt".to_string(), format!("{url}/missing.json5")].map(Into::into))
+        .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .starts_with(&format!("Unable to fetch URL '{url}/missing.json5'")));
+}
+
+#[test]
+fn sort_by_start_keeps_schedule() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "Late", startDate: "2022-01-10", duration: 1, resource: 0 },
+        { title: "Early", startDate: "2022-01-03", duration: 2, resource: 1 },
+        { title: "Middle", duration: 1 },
+      ],
+    }"#;
+    let log = TestLogger::default();
+    let schedule = |args: &[&str]| -> Vec<(String, String, String)> {
+        let output = run(&log, chart, &[&["--format", "json"], args].concat()).unwrap();
+        let schedule: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        schedule["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["title"].as_str().unwrap().to_string(),
+                    item["startDate"].as_str().unwrap().to_string(),
+                    item["endDate"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+    let unsorted = schedule(&[]);
+    let sorted = schedule(&["--sort", "start"]);
+    let titles: Vec<&str> = sorted.iter().map(|item| item.0.as_str()).collect();
+
+    assert_eq!(titles, ["Early", "Middle", "Late"]);
+    assert!(sorted.windows(2).all(|items| items[0].1 <= items[1].1));
+
+    for item in sorted.iter() {
+        assert!(unsorted.contains(item));
+    }
+}
+
+#[test]
+fn fragment_is_one_prefixed_group() {
+    let fragment = run(
+        &TestLogger::default(),
+        CHART,
+        &["--fragment", "--style-prefix", "a-", "--scale", "0.5"],
+    )
+    .unwrap();
+    let root = &elements(&fragment, "g")[0];
+
+    assert!(!fragment.contains("<svg"));
+    assert!(fragment.starts_with("<g "));
+    assert!(fragment.ends_with("</g>"));
+    assert_eq!(root["class"], "a-chart");
+    assert_eq!(root["transform"], "translate(0, 0) scale(0.5)");
+    assert!(style_rule(&fragment, ".a-item").is_some());
+    assert!(style_rule(&fragment, ".item").is_none());
+}
+
+#[test]
+fn class_prefix_applies_to_styles_and_elements() {
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &[
+            "--class-prefix",
+            "p-",
+            "--extra-style",
+            ".late.item, .note > .late{fill:red; font-size:10.5pt;}",
+        ],
+    )
+    .unwrap();
+
+    assert!(style_rule(&svg, ".p-title").is_some());
+    assert!(style_rule(&svg, ".title").is_none());
+    assert_eq!(with_class(&svg, "text", "p-title").len(), 1);
+    assert!(with_class(&svg, "text", "title").is_empty());
+    assert_eq!(
+        style_rule(&svg, ".p-late.p-item, .p-note > .p-late"),
+        Some("fill:red; font-size:10.5pt;")
+    );
+}
+
+#[test]
+fn legend_stats_count_resource_tasks() {
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &["--legend", "--legend-stats"],
+    )
+    .unwrap();
+    let labels: Vec<String> = texts(&svg)
+        .into_iter()
+        .filter(|text| text.contains(" tasks, "))
+        .collect();
+
+    // The second item has the resource of the first
+    assert_eq!(labels, ["Alice (2 tasks, 5 days)", "Bob (1 tasks, 1 days)"]);
+}
+
+#[test]
+fn verify_prints_ok_or_fails() {
+    let log = TestLogger::default();
+    let good_path = chart_file(CHART, "json5");
+    let bad_path = chart_file(&CHART.replace("resource: 1", "resource: 2"), "json5");
+    let verify = |path: &PathBuf| {
+        GanttChartTool::new(&log).run(["gantt", path.to_str().unwrap(), "--verify"].map(Into::into))
+    };
+
+    verify(&good_path).unwrap();
+
+    assert_eq!(*log.output.borrow(), ["OK"]);
+    assert!(verify(&bad_path).is_err());
+    assert_eq!(*log.output.borrow(), ["OK"]);
+
+    std::fs::remove_file(good_path).unwrap();
+    std::fs::remove_file(bad_path).unwrap();
+}
+
+#[test]
+fn all_errors_are_reported_together() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "First", duration: 2, resource: 0 },
+        { title: "Second", duration: -3 },
+        { title: "Third", duration: 1, resource: 4 },
+      ],
+    }"#;
+    let message = run(&TestLogger::default(), chart, &[])
+        .unwrap_err()
+        .to_string();
+
+    assert_eq!(
+        message.lines().collect::<Vec<_>>(),
+        [
+            "First item must contain a start date",
+            "Item 'Second' has a negative duration",
+            "Item 'Third' resource index 4 is out of range, it must be in 0..1",
+        ]
+    );
+}
+
+#[test]
+fn half_day_task_has_half_width_bar() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob", "Carol"],
+      items: [
+        { title: "Day", startDate: "2022-01-03", duration: 1, resource: 0 },
+        { title: "Half day", startDate: "2022-01-03", duration: 0.5, resource: 1 },
+        { title: "Moment", startDate: "2022-01-03", duration: 0.01, resource: 2 },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &[]).unwrap();
+    let width =
+        |class: &str| -> f32 { with_class(&svg, "rect", class)[0]["width"].parse().unwrap() };
+
+    assert!((width("resource-1-closed") / width("resource-0-closed") - 0.5).abs() < 0.01);
+    // Still visible, however short
+    assert_eq!(width("resource-2-closed"), 1.0);
+}
+
+#[test]
+fn rtl_mirrors_bars() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &[]).unwrap();
+    let rtl_svg = run(&log, CHART, &["--rtl"]).unwrap();
+    let width: f32 = elements(&svg, "svg")[0]["width"].parse().unwrap();
+    let bars = with_class(&svg, "rect", "resource-0-closed");
+    let rtl_bars = with_class(&rtl_svg, "rect", "resource-0-closed");
+
+    assert_eq!(bars.len(), 2);
+
+    for (bar, rtl_bar) in bars.iter().zip(rtl_bars.iter()) {
+        let (x, bar_width): (f32, f32) = (bar["x"].parse().unwrap(), bar["width"].parse().unwrap());
+        let rtl_x: f32 = rtl_bar["x"].parse().unwrap();
+
+        // The centers of the bars are the same distance either side of the middle
+        assert!(((rtl_x + bar_width / 2.0) - (width - x - bar_width / 2.0)).abs() < 0.02);
+        assert_eq!(rtl_bar["width"], bar["width"]);
+    }
+}
+
+#[test]
+fn corner_radius_sets_bar_corners() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &["--corner-radius", "7.5"]).unwrap();
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[0];
+
+    assert_eq!((bar["rx"], bar["ry"]), ("7.5", "7.5"));
+
+    let svg = run(&log, CHART, &[]).unwrap();
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[0];
+
+    assert_eq!((bar["rx"], bar["ry"]), ("3", "3"));
+    assert!(run(&log, CHART, &["--corner-radius=-1"]).is_err());
+}
+
+#[test]
+fn end_date_ends_on_that_date() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "Dates", startDate: "2022-01-03", endDate: "2022-01-17", resource: 0 },
+        { title: "Next", duration: 1 },
+        { title: "Duration", startDate: "2022-01-03", duration: 14, resource: 1 },
+      ],
+    }"#;
+    let render_data = layout(chart, &[]);
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    // Across two weekends, and the next item starts on the end date
+    assert_eq!(render_data.rows[0].end_date, date("2022-01-17"));
+    assert_eq!(render_data.rows[1].start_date, date("2022-01-17"));
+    assert_eq!(render_data.rows[0].length, render_data.rows[2].length);
+}
+
+#[test]
+fn diagnostics_json_lists_warnings() {
+    let log = TestLogger::default();
+    let input_path = chart_file(WARNING_CHART, "json5");
+    let output_path = input_path.with_extension("svg");
+    let json_path = input_path.with_extension("json");
+
+    GanttChartTool::new(&log)
+        .run(
+            [
+                "gantt".as_ref(),
+                input_path.as_os_str(),
+                output_path.as_os_str(),
+                format!("--diagnostics-json={}", json_path.display()).as_ref(),
+            ]
+            .iter()
+            .map(Into::into),
+        )
+        .unwrap();
+
+    let json = std::fs::read_to_string(&json_path).unwrap();
+
+    for path in [input_path, output_path, json_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    let diagnostics: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    // Starting early is not reported again as the resource being over-allocated
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["level"], "warning");
+    assert_eq!(diagnostics[0]["item"], "Early");
+    assert_eq!(
+        diagnostics[0]["message"],
+        "Item 'Early' starts on 2022-01-05 before the previous item 'First' ends on 2022-01-10"
+    );
+
+    // Without an equals sign the file after it is the input, not where the diagnostics go
+    let cli = Cli::try_parse_from(["gantt", "--diagnostics-json", "chart.json5"]).unwrap();
+
+    assert_eq!(cli.input_file, Some(PathBuf::from("chart.json5")));
+    assert_eq!(cli.diagnostics_json, Some(PathBuf::from("-")));
+}
+
+#[test]
+fn a4_landscape_sizes_document_to_page() {
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &["--page", "a4", "--landscape"],
+    )
+    .unwrap();
+    let document = &elements(&svg, "svg")[0];
+
+    assert_eq!(document["width"], "297mm");
+    assert_eq!(document["height"], "210mm");
+    assert_eq!(document["viewBox"], "0 0 297 210");
+
+    let background = &with_class(&svg, "rect", "background")[0];
+
+    assert_eq!(background["width"], "297");
+    assert_eq!(background["height"], "210");
+}
+
+#[test]
+fn week_lines_fall_on_mondays() {
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &["--week-lines", "--week-numbers"],
+    )
+    .unwrap();
+    let xs: Vec<f32> = with_class(&svg, "line", "week-line")
+        .iter()
+        .map(|line| line["x1"].parse().unwrap())
+        .collect();
+
+    // January 2022 has Mondays on the 3rd, 10th, 17th, 24th and 31st
+    assert_eq!(xs.len(), 5);
+
+    let day_width = 200.0 / 31.0;
+
+    for (x, day) in xs.iter().zip([3.0, 10.0, 17.0, 24.0, 31.0]) {
+        assert!((x - (220.0 + (day - 1.0) * day_width)).abs() < 0.01);
+    }
+
+    assert_eq!(with_class(&svg, "text", "week-label").len(), 5);
+}
+
+#[test]
+fn toml_chart_matches_json5() {
+    let toml_chart = r#"
+title = "Test Chart"
+resources = ["Alice", "Bob"]
+markedDate = { date = "2022-01-05" }
+
+[[items]]
+title = "First"
+startDate = "2022-01-03"
+duration = 2
+resource = 0
+
+[[items]]
+title = "Second"
+duration = 3
+
+[[items]]
+title = "Third"
+startDate = "2022-01-10"
+duration = 1
+resource = 1
+"#;
+    let json5_chart = CHART.replacen('{', "{ markedDate: { date: \"2022-01-05\" },", 1);
+    let read = |content: &str, format| {
+        serde_json::to_value(
+            GanttChartTool::read_chart_file(Box::new(io::Cursor::new(content.to_owned())), format)
+                .unwrap(),
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        read(toml_chart, InputFormat::Toml),
+        read(&json5_chart, InputFormat::Json5)
+    );
+
+    // The format comes from the file extension
+    let path = chart_file(toml_chart, "toml");
+    let svg = GanttChartTool::new(&TestLogger::default())
+        .run_to_string(["gantt", path.to_str().unwrap()].iter().map(Into::into))
+        .unwrap();
+
+    std::fs::remove_file(path).unwrap();
+
+    let bars = |svg: &str| -> Vec<(String, String)> {
+        elements(svg, "rect")
+            .iter()
+            .filter(|rect| rect.contains_key("rx"))
+            .map(|rect| (rect["x"].to_owned(), rect["width"].to_owned()))
+            .collect()
+    };
+
+    assert_eq!(
+        bars(&svg),
+        bars(&run(&TestLogger::default(), &json5_chart, &[]).unwrap())
+    );
+    assert_eq!(bars(&svg).len(), 3);
+}
+
+#[test]
+fn builder_checks_first_task() {
+    let start_date = NaiveDate::from_ymd_opt(2022, 1, 3);
+    let chart_data = ChartDataBuilder::new()
+        .title("Built Chart")
+        .add_resource("Alice")
+        .add_task("First", 2.0, start_date, Some(0))
+        .add_task("Second", 3.0, None, None)
+        .add_milestone("Done", None, None)
+        .build()
+        .unwrap();
+
+    assert_eq!(chart_data.title, "Built Chart");
+    assert_eq!(chart_data.items.len(), 3);
+    assert_eq!(chart_data.items[0].start_date, start_date);
+
+    let err = ChartDataBuilder::new()
+        .title("Built Chart")
+        .add_resource("Alice")
+        .add_task("First", 2.0, None, Some(0))
+        .build()
+        .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("First item must contain a start date"));
+
+    // Pinned a month either side of the items
+    let chart_data = ChartDataBuilder::new()
+        .title("Built Chart")
+        .chart_start(NaiveDate::from_ymd_opt(2021, 12, 1).unwrap())
+        .chart_end(NaiveDate::from_ymd_opt(2022, 2, 28).unwrap())
+        .add_resource("Alice")
+        .add_task("First", 2.0, start_date, Some(0))
+        .add_task("Second", 3.0, None, None)
+        .build()
+        .unwrap();
+    let cli = Cli::try_parse_from(["gantt"]).unwrap();
+    let render_data = GanttChartTool::new(&TestLogger::default())
+        .process_chart_data(&cli.layout_options().unwrap(), &chart_data)
+        .unwrap();
+
+    assert_eq!(
+        render_data
+            .cols
+            .iter()
+            .map(|col| col.month_name.as_str())
+            .collect::<Vec<_>>(),
+        ["Dec", "Jan", "Feb"]
+    );
+}
+
+#[test]
+fn compressed_weekends_take_no_width() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "Weekdays", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Over the weekend", startDate: "2022-01-06", duration: 4, resource: 1 },
+      ],
+    }"#;
+    let rows = layout(chart, &["--compress-weekends"]).rows;
+
+    // Thursday and Friday, with the weekend before Monday taking no space
+    assert_eq!(
+        rows[1].end_date,
+        NaiveDate::from_ymd_opt(2022, 1, 10).unwrap()
+    );
+    assert!((rows[0].length.unwrap() - rows[1].length.unwrap()).abs() < 0.01);
+
+    let rows = layout(chart, &[]).rows;
+
+    assert!(rows[1].length.unwrap() > rows[0].length.unwrap() * 1.5);
+}
+
+#[test]
+fn resource_colors_match_styles() {
+    let render_data = layout(CHART, &[]);
+    let schedule = GanttChartTool::build_schedule(&render_data);
+
+    assert_eq!(schedule.resource_colors.len(), 2);
+
+    for (i, resource_color) in schedule.resource_colors.iter().enumerate() {
+        let color = &resource_color.color;
+
+        assert_eq!(resource_color.name, ["Alice", "Bob"][i]);
+        assert!(render_data.styles.contains(&format!(
+            ".resource-{i}-closed{{stroke-width:1; stroke:{color}; fill:{color};}}"
+        )));
+    }
+
+    // Written out as an object with a CSS color
+    let json = serde_json::to_value(&schedule).unwrap();
+    let color = json["resourceColors"][0]["color"].as_str().unwrap();
+
+    assert_eq!(json["resourceColors"][0]["name"], "Alice");
+    assert!(color.len() == 7 && colors::parse(color).is_some());
+}
+
+#[test]
+fn subtitle_moves_rows_down() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &[]).unwrap();
+    let subtitle_svg = run(
+        &log,
+        &CHART.replacen('{', "{ subtitle: \"Phase One\",", 1),
+        &[],
+    )
+    .unwrap();
+    let bar_y = |svg: &str| -> f32 {
+        with_class(svg, "rect", "resource-0-closed")[0]["y"]
+            .parse()
+            .unwrap()
+    };
+
+    assert_eq!(with_class(&subtitle_svg, "text", "subtitle").len(), 1);
+    assert!(texts(&subtitle_svg).contains(&"Phase One".to_string()));
+    assert_eq!(bar_y(&subtitle_svg) - bar_y(&svg), 20.0);
+}
+
+#[test]
+fn circle_milestone_is_centered_on_date() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Done" },
+      ],
+    }"#;
+    let svg = run(
+        &TestLogger::default(),
+        chart,
+        &["--milestone-shape", "circle", "--milestone-size", "1.5"],
+    )
+    .unwrap();
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[0];
+    let circles = with_class(&svg, "circle", "milestone");
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+
+    assert_eq!(circles.len(), 1);
+    // At the end of the first task, in the middle of the next row, with each rounded to two places
+    assert!((value(circles[0]["cx"]) - (value(bar["x"]) + value(bar["width"]))).abs() < 0.02);
+    assert_eq!(
+        value(circles[0]["cy"]),
+        value(bar["y"]) + 30.0 + value(bar["height"]) / 2.0
+    );
+    assert_eq!(value(circles[0]["r"]), value(bar["height"]) / 2.0 * 1.5);
+}
+
+#[test]
+fn dump_layout_goes_to_stderr() {
+    let log = TestLogger::default();
+
+    assert_eq!(run(&log, CHART, &["--dump-layout=json"]).unwrap(), "");
+    assert!(log.output.borrow().is_empty());
+
+    let render_data: serde_json::Value = serde_json::from_str(&log.debug.borrow()[0]).unwrap();
+
+    assert_eq!(render_data["rows"].as_array().unwrap().len(), 3);
+    assert_eq!(render_data["cols"].as_array().unwrap().len(), 1);
+
+    // Without an equals sign the file after it is the input, not the format
+    let cli = Cli::try_parse_from(["gantt", "--dump-layout", "chart.json5"]).unwrap();
+
+    assert_eq!(cli.input_file, Some(PathBuf::from("chart.json5")));
+    assert_eq!(cli.dump_layout, Some(LayoutFormat::Debug));
+}
+
+// Written before there was a debug method, which does nothing unless provided
+struct OutputLogger(RefCell<Vec<String>>);
+
+impl GanttChartLog for OutputLogger {
+    fn output(&self, args: Arguments) {
+        self.0.borrow_mut().push(args.to_string());
+    }
+    fn warning(&self, _args: Arguments) {}
+    fn error(&self, _args: Arguments) {}
+}
+
+#[test]
+fn logger_without_debug_drops_layout_dump() {
+    let log = OutputLogger(RefCell::new(vec![]));
+    let path = chart_file(CHART, "json5");
+    let result = GanttChartTool::new(&log).run_to_string(
+        ["gantt", path.to_str().unwrap(), "--dump-layout"]
+            .iter()
+            .map(Into::into),
+    );
+
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(result.unwrap(), "");
+    assert!(log.0.borrow().is_empty());
+}
+
+#[test]
+fn lag_and_lead_move_start_in_working_days() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 3, resource: 0 },
+        { title: "Lagged", lag: 2 },
+        { title: "Monday", startDate: "2022-01-10", duration: 1, resource: 1 },
+        { title: "Half", startDate: "2022-01-03", duration: 2.5, resource: 0 },
+        { title: "Led", lag: -1 },
+        { title: "Wednesday", startDate: "2022-01-05", duration: 1, resource: 1 },
+      ],
+    }"#;
+    let rows = layout(chart, &["--critical-path"]).rows;
+
+    // First ends on Thursday, so two working days on is past the weekend
+    assert_eq!(
+        rows[1].start_date,
+        NaiveDate::from_ymd_opt(2022, 1, 10).unwrap()
+    );
+    assert_eq!(rows[1].offset, rows[2].offset);
+    // Half way through Wednesday, a day's lead starts at the beginning of Tuesday
+    assert_eq!(
+        rows[4].start_date,
+        NaiveDate::from_ymd_opt(2022, 1, 4).unwrap()
+    );
+    assert!((rows[5].offset - rows[4].offset - 200.0 / 31.0).abs() < 0.01);
+}
+
+#[test]
+fn note_is_on_its_row() {
+    let chart = CHART.replacen(
+        "{ title: \"Second\",",
+        "{ title: \"Second\", note: \"pending approval\",",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+    let notes = with_class(&svg, "text", "note");
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[1];
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+
+    assert_eq!(notes.len(), 1);
+    assert!(texts(&svg).contains(&"pending approval".to_string()));
+    assert_eq!(
+        value(notes[0]["y"]),
+        value(bar["y"]) + value(bar["height"]) / 2.0
+    );
+    assert!(value(notes[0]["x"]) > value(bar["x"]) + value(bar["width"]));
+
+    // Cut short at the edge of the chart
+    let chart = CHART.replacen(
+        "{ title: \"Second\",",
+        &format!(
+            "{{ title: \"Second\", note: \"{}\",",
+            "approval ".repeat(20)
+        ),
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+
+    assert!(texts(&svg).iter().any(|text| text.ends_with('…')));
+}
+
+#[test]
+fn uniform_months_have_equal_widths() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "January", startDate: "2022-01-03", duration: 5, resource: 0 },
+        { title: "February", startDate: "2022-02-15", duration: 1 },
+        { title: "March", startDate: "2022-03-01", duration: 5 },
+      ],
+    }"#;
+    let render_data = layout(chart, &["--uniform-months"]);
+    let widths: Vec<f32> = render_data.cols.iter().map(|col| col.width).collect();
+
+    assert_eq!(widths, vec![200.0; 3]);
+    // Half way through the short month is half way across its column
+    let february_x = render_data.gutter.left + render_data.title_width + 200.0;
+
+    assert!((render_data.rows[1].offset - (february_x + 200.0 * 14.0 / 28.0)).abs() < 0.01);
+}
+
+#[test]
+fn csv_chart_infers_resources() {
+    let csv = "\
+Title, Start, Duration, Resource, Milestone
+\"Design, first pass\", 2022-01-03, 2, Alice
+Build, , 3, Bob
+Review, , , , yes
+";
+    let chart_data = GanttChartTool::read_csv_chart(csv).unwrap();
+    let items: Vec<_> = chart_data
+        .items
+        .iter()
+        .map(|item| {
+            (
+                item.title.as_str(),
+                item.start_date,
+                item.duration,
+                chart_data.resource_index(item),
+            )
+        })
+        .collect();
+
+    assert_eq!(chart_data.title, "Gantt Chart");
+    assert_eq!(
+        serde_json::to_value(&chart_data.resources).unwrap(),
+        serde_json::json!(["Alice", "Bob"])
+    );
+    assert_eq!(
+        items,
+        vec![
+            (
+                "Design, first pass",
+                NaiveDate::from_ymd_opt(2022, 1, 3),
+                Some(2.0),
+                Some(0)
+            ),
+            ("Build", None, Some(3.0), Some(1)),
+            ("Review", None, None, None),
+        ]
+    );
+}
+
+#[test]
+fn task_guides_mark_bar_ends() {
+    let svg = run(&TestLogger::default(), CHART, &["--task-guides"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let guide_xs: Vec<f32> = with_class(&svg, "line", "task-guide")
+        .iter()
+        .map(|line| value(line["x1"]))
+        .collect();
+    let bar_xs: Vec<f32> = ["resource-0-closed", "resource-1-closed"]
+        .iter()
+        .flat_map(|class| with_class(&svg, "rect", class))
+        .flat_map(|rect| [value(rect["x"]), value(rect["x"]) + value(rect["width"])])
+        .collect();
+
+    // The bars and their guides are both in row order
+    assert_eq!(guide_xs.len(), 6);
+
+    for (guide_x, bar_x) in guide_xs.iter().zip(bar_xs.iter()) {
+        assert!((guide_x - bar_x).abs() < 0.02);
+    }
+}
+
+#[test]
+fn order_changes_rows_but_not_offsets() {
+    let ordered = |orders: [i32; 3]| {
+        let mut chart = CHART.to_string();
+
+        for (title, order) in ["First", "Second", "Third"].iter().zip(orders) {
+            chart = chart.replacen(
+                &format!("{{ title: \"{title}\","),
+                &format!("{{ title: \"{title}\", order: {order},"),
+                1,
+            );
+        }
+
+        let mut render_data = layout(&chart, &[]);
+
+        render_data.sort_rows(RowOrder::None);
+        render_data
+            .rows
+            .iter()
+            .map(|row| (row.title.clone(), row.offset, row.length))
+            .collect::<Vec<_>>()
+    };
+    let mut rows = ordered([1, 2, 3]);
+    let reversed_rows = ordered([3, 2, 1]);
+
+    assert_eq!(rows[0].0, "First");
+    rows.reverse();
+    assert_eq!(rows, reversed_rows);
+}
+
+#[test]
+fn summary_bar_weights_completion_by_days() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0, percentComplete: 50 },
+        { title: "Second", duration: 3, percentComplete: 100 },
+        { title: "Done", percentComplete: 0 },
+        { title: "Third", startDate: "2022-01-10", duration: 1, resource: 1 },
+      ],
+    }"#;
+    let log = TestLogger::default();
+    let svg = run(&log, chart, &["--summary"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let track = &with_class(&svg, "rect", "summary-track")[0];
+    let bar = &with_class(&svg, "rect", "summary")[0];
+
+    // One day of the first task and all three of the second, out of six
+    assert!((value(bar["width"]) / value(track["width"]) - 4.0 / 6.0).abs() < 0.01);
+    assert!(texts(&svg).contains(&"Overall 67%".to_string()));
+
+    let height = |svg: &str| value(elements(svg, "svg")[0]["height"]);
+
+    assert_eq!(height(&svg) - height(&run(&log, chart, &[]).unwrap()), 30.0);
+}
+
+#[test]
+fn long_legend_name_pushes_next_entry() {
+    let log = TestLogger::default();
+    let gap = |first_name: &str| -> f32 {
+        let svg = run(
+            &log,
+            &CHART.replacen("\"Alice\"", &format!("\"{first_name}\""), 1),
+            &["--legend-position", "top"],
+        )
+        .unwrap();
+        let labels = with_class(&svg, "text", "legend-entry");
+
+        labels[1]["x"].parse::<f32>().unwrap() - labels[0]["x"].parse::<f32>().unwrap()
+    };
+
+    assert!(gap("Alexandra Montgomery-Smith") > gap("Al") + 100.0);
+}
+
+#[test]
+fn embedded_source_round_trips() {
+    let chart = CHART.replacen("\"Second\"", "\"Second <&> ]]> end\"", 1);
+    let svg = run(&TestLogger::default(), &chart, &["--embed-source"]).unwrap();
+    let metadata = &svg
+        [svg.find("<metadata>").unwrap() + "<metadata>".len()..svg.find("</metadata>").unwrap()];
+    // Put the CDATA sections back together
+    let source: String = metadata
+        .trim()
+        .strip_prefix("<![CDATA[")
+        .and_then(|source| source.strip_suffix("]]>"))
+        .unwrap()
+        .replace("]]><![CDATA[", "");
+    let chart_data: ChartData = serde_json::from_str(&source).unwrap();
+
+    assert_eq!(chart_data.items[1].title, "Second <&> ]]> end");
+    assert_eq!(
+        serde_json::to_value(&chart_data).unwrap(),
+        serde_json::to_value(json5::from_str::<ChartData>(&chart).unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn segments_share_a_row() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        {
+          title: "Split",
+          startDate: "2022-01-03",
+          resource: 0,
+          segments: [{ duration: 2 }, { startDate: "2022-01-10", duration: 2 }],
+        },
+        { title: "Done" },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &[]).unwrap();
+    let bars = with_class(&svg, "rect", "resource-0-closed");
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0]["y"], bars[1]["y"]);
+    // From Wednesday to the next Monday
+    assert!(
+        (value(bars[1]["x"]) - value(bars[0]["x"]) - value(bars[0]["width"]) - 5.0 * 200.0 / 31.0)
+            .abs()
+            < 0.02
+    );
+    assert_eq!(
+        layout(chart, &[]).rows[0].end_date,
+        NaiveDate::from_ymd_opt(2022, 1, 12).unwrap()
+    );
+}
+
+#[test]
+fn min_bar_width_widens_only_short_bars() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "Moment", startDate: "2022-01-03", duration: 0.1, resource: 0 },
+        { title: "Long", startDate: "2022-01-03", duration: 10, resource: 1 },
+      ],
+    }"#;
+    let rows = layout(chart, &["--min-bar-width", "8"]).rows;
+    let unclamped_rows = layout(chart, &[]).rows;
+
+    assert_eq!(rows[0].length, Some(8.0));
+    assert_eq!(rows[0].offset, unclamped_rows[0].offset);
+    assert_eq!(rows[1].length, unclamped_rows[1].length);
+}
+
+#[test]
+fn snap_pixels_rounds_coordinates() {
+    let log = TestLogger::default();
+    let args = [
+        "--margin-top",
+        "10.3",
+        "--margin-left",
+        "7.6",
+        "--week-lines",
+    ];
+    let svg = run(&log, CHART, &[&args[..], &["--snap-pixels"]].concat()).unwrap();
+    let unsnapped_svg = run(&log, CHART, &args).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let rects = elements(&svg, "rect");
+
+    assert_eq!(rects.len(), elements(&unsnapped_svg, "rect").len());
+
+    for (rect, unsnapped_rect) in rects.iter().zip(elements(&unsnapped_svg, "rect").iter()) {
+        for name in ["x", "y", "width", "height"] {
+            if let Some(snapped) = rect.get(name) {
+                assert_eq!(value(snapped).fract(), 0.0);
+                assert!((value(snapped) - value(unsnapped_rect[name])).abs() <= 1.0);
+            }
+        }
+    }
+
+    // The thin week lines cover exactly one pixel
+    for line in with_class(&svg, "line", "week-line") {
+        assert_eq!(value(line["x1"]).fract(), 0.5);
+        assert_eq!(value(line["y1"]).fract(), 0.0);
+    }
+}
+
+#[test]
+fn sunday_weeks_start_a_day_earlier() {
+    let week_xs = |week_start: &str| -> Vec<f32> {
+        layout(CHART, &["--week-lines", "--week-start", week_start])
+            .week_offsets
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect()
+    };
+    let monday_xs = week_xs("mon");
+    let sunday_xs = week_xs("sun");
+
+    // January 2022 starts on a Saturday, so both have five weeks starting in it
+    assert_eq!(monday_xs.len(), 5);
+    assert_eq!(sunday_xs.len(), 5);
+
+    for (monday_x, sunday_x) in monday_xs.iter().zip(sunday_xs.iter()) {
+        assert!((monday_x - sunday_x - 200.0 / 31.0).abs() < 0.01);
+    }
+}
+
+#[test]
+fn swimlane_spans_resource_rows() {
+    let svg = run(&TestLogger::default(), CHART, &["--swimlanes"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let alice_bars = with_class(&svg, "rect", "resource-0-closed");
+    let bob_bars = with_class(&svg, "rect", "resource-1-closed");
+    let alice_lanes = with_class(&svg, "rect", "swimlane-0");
+    let bob_lanes = with_class(&svg, "rect", "swimlane-1");
+
+    assert_eq!((alice_lanes.len(), bob_lanes.len()), (1, 1));
+    // From the top of the first row to the bottom of the last, with the bars inside the row gutters
+    assert_eq!(value(alice_lanes[0]["y"]), value(alice_bars[0]["y"]) - 5.0);
+    assert_eq!(
+        value(alice_lanes[0]["y"]) + value(alice_lanes[0]["height"]),
+        value(alice_bars[1]["y"]) + value(alice_bars[1]["height"]) + 5.0
+    );
+    assert_eq!(
+        value(alice_lanes[0]["y"]) + value(alice_lanes[0]["height"]),
+        value(bob_lanes[0]["y"])
+    );
+    assert_eq!(
+        value(bob_lanes[0]["height"]),
+        value(bob_bars[0]["height"]) + 10.0
+    );
+    assert!(style_rule(&svg, ".swimlane-0").is_some());
+}
+
+#[test]
+fn gradient_bars_use_resource_gradients() {
+    let svg = run(&TestLogger::default(), CHART, &["--bar-style", "gradient"]).unwrap();
+    let gradients = elements(&svg, "linearGradient");
+
+    assert_eq!(gradients.len(), 2);
+
+    for (i, gradient) in gradients.iter().enumerate() {
+        assert_eq!(gradient["id"], format!("gradient-{i}"));
+        // After the flat fill, so that it wins
+        let flat_fill = svg
+            .find(&format!(".resource-{i}-closed{{stroke-width:1;"))
+            .unwrap();
+        let gradient_fill = svg
+            .find(&format!(".resource-{i}-closed{{fill:url(#gradient-{i});}}"))
+            .unwrap();
+
+        assert!(flat_fill < gradient_fill);
+        assert!(!with_class(&svg, "rect", &format!("resource-{i}-closed")).is_empty());
+    }
+
+    // The outlines of open bars stay flat
+    assert!(!svg.contains("-open{fill:url("));
+}
+
+#[test]
+fn bad_resource_index_names_item_and_range() {
+    let chart = CHART.replacen("resource: 1", "resource: 5", 1);
+    let err = run(&TestLogger::default(), &chart, &[]).unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("Item 'Third' resource index 5 is out of range, it must be in 0..2"));
+}
+
+#[test]
+fn autofill_spans_to_next_anchor() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Filler" },
+        { title: "Anchor", startDate: "2022-01-12", duration: 1 },
+      ],
+    }"#;
+    let rows = layout(chart, &["--autofill-durations"]).rows;
+
+    assert!(!rows[1].milestone);
+    assert_eq!(
+        rows[1].start_date,
+        NaiveDate::from_ymd_opt(2022, 1, 5).unwrap()
+    );
+    assert_eq!(
+        rows[1].end_date,
+        NaiveDate::from_ymd_opt(2022, 1, 12).unwrap()
+    );
+    assert!((rows[1].offset + rows[1].length.unwrap() - rows[2].offset).abs() < 0.01);
+
+    // Without the flag it is a milestone
+    assert!(layout(chart, &[]).rows[1].milestone);
+}
+
+#[test]
+fn bar_past_chart_end_is_clipped() {
+    let chart = r#"{
+      title: "Test Chart",
+      chartEnd: "2022-01-31",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Long", startDate: "2022-01-24", duration: 20 },
+      ],
+    }"#;
+    let log = TestLogger::default();
+    let svg = run(&log, chart, &[]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let overflowing = with_class(&svg, "rect", "overflow");
+    let right_edge = value(elements(&svg, "svg")[0]["width"]) - 10.0;
+
+    // The chart still runs to the end of the month, so that is where the bar is cut off
+    assert_eq!(
+        *log.warnings.borrow(),
+        ["Chart end 2022-01-31 is before the last item end 2022-02-14, the items are cut off at the end of its month"]
+    );
+    assert_eq!(overflowing.len(), 1);
+    assert!(
+        (value(overflowing[0]["x"]) + value(overflowing[0]["width"]) - right_edge).abs() < 0.01
+    );
+    assert_eq!(with_class(&svg, "path", "overflow-arrow").len(), 1);
+}
+
+#[test]
+fn baseline_shows_slipped_plan() {
+    let chart = CHART.replacen(
+        "{ title: \"Third\",",
+        "{ title: \"Third\", baselineStart: \"2022-01-06\", baselineDuration: 1,",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &["--baseline"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let baselines = with_class(&svg, "rect", "baseline");
+    let bar = &with_class(&svg, "rect", "resource-1-closed")[0];
+
+    assert_eq!(baselines.len(), 1);
+    // In the gutter under the bar, two working days earlier
+    assert!(value(baselines[0]["y"]) > value(bar["y"]) + value(bar["height"]));
+    assert!(value(baselines[0]["y"]) < value(bar["y"]) + 30.0);
+    assert!((value(bar["x"]) - value(baselines[0]["x"]) - 4.0 * 200.0 / 31.0).abs() < 0.02);
+    assert_eq!(baselines[0]["width"], bar["width"]);
+}
+
+#[test]
+fn arrow_for_each_item_following_on() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Second", duration: 3 },
+        { title: "Overlap", lag: -2, duration: 1, resource: 1 },
+        { title: "Third", startDate: "2022-01-17", duration: 1 },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &["--arrows"]).unwrap();
+    let arrows = with_class(&svg, "path", "dependency-arrow");
+
+    // Only the items without a start date follow on from the one before
+    assert_eq!(arrows.len(), 2);
+    assert_eq!(with_class(&svg, "path", "dependency-arrow-head").len(), 2);
+    // Into the start of the bar, even though it starts before the end of the item before
+    let bar = &with_class(&svg, "rect", "resource-1-closed")[0];
+
+    assert!(arrows[1]["d"].ends_with(&format!(
+        "L{},{}",
+        bar["x"],
+        bar["y"].parse::<f32>().unwrap() + 10.0
+    )));
+}
+
+#[test]
+fn date_format_applies_to_dates() {
+    let chart = CHART.replacen('{', "{ markedDate: \"2022-01-05\",", 1);
+    let svg = run(
+        &TestLogger::default(),
+        &chart,
+        &["--date-format", "%d %b %Y", "--show-bounds"],
+    )
+    .unwrap();
+    let marker = &svg[svg.find("class=\"marker\"").unwrap()..];
+
+    // In the tooltip on the marker, and under the project start
+    assert_eq!(
+        marker[marker.find("<title>").unwrap() + "<title>".len()..marker.find("</title>").unwrap()]
+            .trim(),
+        "05 Jan 2022"
+    );
+    assert!(texts(&svg).contains(&"03 Jan 2022".to_string()));
+    assert!(
+        run(&TestLogger::default(), &chart, &["--date-format", "%Q"])
+            .unwrap_err()
+            .to_string()
+            .contains("Date format '%Q' is not valid")
+    );
+}
+
+#[test]
+fn bars_are_in_a_named_layer() {
+    let svg = run(&TestLogger::default(), CHART, &[]).unwrap();
+    let groups = elements(&svg, "g");
+    let rows_g = groups
+        .iter()
+        .find(|group| group.get("id") == Some(&"rows"))
+        .unwrap();
+
+    assert_eq!(rows_g["inkscape:groupmode"], "layer");
+    assert_eq!(rows_g["inkscape:label"], "Rows");
+
+    // The bars are inside it, up to the next layer, with a list item for each row
+    let rows_start = svg.find("<g id=\"rows\"").unwrap();
+    let rows_svg = &svg[rows_start..];
+    let rows_svg = &rows_svg[..rows_svg[1..]
+        .find("<g id=")
+        .map_or(rows_svg.len(), |end| end + 1)];
+
+    assert_eq!(rows_g["role"], "list");
+    assert_eq!(with_class(rows_svg, "rect", "resource-0-closed").len(), 2);
+    assert_eq!(
+        elements(rows_svg, "g")
+            .iter()
+            .filter(|group| group.get("role") == Some(&"listitem"))
+            .count(),
+        3
+    );
+    assert!(svg.contains("xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\""));
+}
+
+#[test]
+fn show_duration_counts_working_days() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &["--show-duration"]).unwrap();
+
+    // From Monday the 3rd to Tuesday the 11th
+    assert!(texts(&svg).contains(&"8 calendar days, 6 working days".to_string()));
+
+    let svg = run(&log, CHART, &["--show-duration", "--calendar-days"]).unwrap();
+
+    assert!(texts(&svg).contains(&"8 calendar days, 8 working days".to_string()));
+}
+
+#[test]
+fn px_per_day_fixes_day_width() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "Month", startDate: "2022-01-03", duration: 30, resource: 0 },
+        { title: "Done" },
+      ],
+    }"#;
+    let render_data = layout(chart, &["--px-per-day", "5"]);
+
+    assert_eq!(render_data.rows[0].length, Some(150.0));
+    assert_eq!(render_data.cols[0].width, 155.0);
+    assert!(
+        Cli::try_parse_from(["gantt", "--px-per-day", "5", "--max-month-width", "100"]).is_err()
+    );
+}
+
+#[test]
+fn mixed_group_resources_warn() {
+    let group = |chart: &str, title: &str| {
+        chart.replacen(
+            &format!("{{ title: \"{title}\","),
+            &format!("{{ title: \"{title}\", group: \"Design\","),
+            1,
+        )
+    };
+    // The second item carries on with the resource of the first
+    let uniform_chart = group(&group(CHART, "First"), "Second");
+    let log = TestLogger::default();
+
+    run(&log, &uniform_chart, &[]).unwrap();
+    assert!(log.warnings.borrow().is_empty());
+
+    let mixed_chart = group(&uniform_chart, "Third");
+
+    run(&log, &mixed_chart, &[]).unwrap();
+    assert_eq!(
+        *log.warnings.borrow(),
+        vec!["Group 'Design' mixes resources, 'First' and 'Third' do not use the same resource"]
+    );
+
+    let log = TestLogger::default();
+
+    run(&log, &mixed_chart, &["--allow-mixed-groups"]).unwrap();
+    assert!(log.warnings.borrow().is_empty());
+}
+
+#[test]
+fn color_saturation_changes_resource_colors() {
+    let colors = |saturation: &str| {
+        layout(
+            CHART,
+            &["--hue-start", "0.3", "--color-saturation", saturation],
+        )
+        .resource_colors()
+    };
+
+    assert_eq!(colors("0.5"), colors("0.5"));
+    assert_ne!(colors("0.5")[0].1, colors("0.9")[0].1);
+    // No saturation is a gray
+    assert_eq!(colors("0")[0].1, 0x808080);
+
+    let cli = Cli::try_parse_from(["gantt", "--color-saturation", "1.5"]).unwrap();
+
+    assert!(cli
+        .layout_options()
+        .unwrap_err()
+        .to_string()
+        .starts_with("Color saturation must be between 0 and 1"));
+}
+
+#[test]
+fn full_value_white_stays_in_range() {
+    assert_eq!(GanttChartTool::hsv_to_rgb(0.0, 0.0, 1.0), 0xffffff);
+    assert_eq!(GanttChartTool::hsv_to_rgb(0.5, 0.0, 1.0), 0xffffff);
+    // Pure red, without the green spilling into it
+    assert_eq!(GanttChartTool::hsv_to_rgb(0.0, 1.0, 1.0), 0xff0000);
+}
+
+#[test]
+fn legend_shows_resource_icon() {
+    let chart = CHART.replacen(
+        "\"Alice\"",
+        "{ name: \"Alice\", icon: \"https://example.com/alice.png\" }",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &["--legend"]).unwrap();
+    let images = elements(&svg, "image");
+
+    // Only for the resource with an icon
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0]["href"], "https://example.com/alice.png");
+    assert!(texts(&svg).contains(&"Alice".to_string()));
+}
+
+#[test]
+fn hours_convert_to_fractional_days() {
+    let chart = r#"{
+      title: "Test Chart",
+      hoursPerDay: 8,
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "Hours", startDate: "2022-01-03", hours: 20, resource: 0 },
+        { title: "Days", startDate: "2022-01-03", duration: 2.5, resource: 1 },
+      ],
+    }"#;
+    let rows = layout(chart, &[]).rows;
+
+    assert_eq!(rows[0].length, rows[1].length);
+    assert!((rows[0].length.unwrap() - 2.5 * 200.0 / 31.0).abs() < 0.01);
+}
+
+#[test]
+fn window_clips_straddling_tasks() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "Before", startDate: "2022-01-03", duration: 5, resource: 0 },
+        { title: "Into", startDate: "2022-02-01", duration: 10 },
+        { title: "Inside", startDate: "2022-02-14", duration: 2 },
+        { title: "Out of", startDate: "2022-02-17", duration: 10 },
+        { title: "After", startDate: "2022-03-14", duration: 5 },
+      ],
+    }"#;
+    let render_data = layout(chart, &["--window", "2022-02-07..2022-02-20"]);
+    let day_width = 200.0 / 31.0;
+    let left = render_data.gutter.left + render_data.title_width;
+    let right = left + 14.0 * day_width;
+    let titles: Vec<&str> = render_data
+        .rows
+        .iter()
+        .map(|row| row.title.as_str())
+        .collect();
+
+    // Only the two weeks of the window, in one column
+    assert_eq!(render_data.cols.len(), 1);
+    assert!((render_data.cols[0].width - 14.0 * day_width).abs() < 0.01);
+    assert_eq!(titles, ["Into", "Inside", "Out of"]);
+
+    let rows = &render_data.rows;
+    let end = |row: &RowRenderData| row.offset + row.length.unwrap();
+
+    // Into ends on Friday the 11th
+    assert_eq!(rows[0].offset, left);
+    assert!((end(&rows[0]) - (left + 4.0 * day_width)).abs() < 0.01);
+    assert!((rows[1].offset - (left + 7.0 * day_width)).abs() < 0.01);
+    assert!((rows[2].offset - (left + 10.0 * day_width)).abs() < 0.01);
+    assert!((end(&rows[2]) - right).abs() < 0.01);
+}
+
+#[test]
+fn kind_overrides_duration() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Sign off", duration: 1, kind: "milestone" },
+        { title: "Task", startDate: "2022-01-10", kind: "task", resource: 1 },
+        { title: "Next", duration: 1 },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &[]).unwrap();
+
+    // A diamond for the milestone despite its duration, and bars for all the tasks
+    assert_eq!(with_class(&svg, "path", "milestone").len(), 1);
+    assert_eq!(with_class(&svg, "rect", "resource-0-closed").len(), 1);
+    assert_eq!(with_class(&svg, "rect", "resource-1-closed").len(), 2);
+
+    // A task without a duration takes a day, and the next item follows on from it
+    let rows = layout(chart, &[]).rows;
+
+    assert!(!rows[2].milestone);
+    assert!((rows[2].length.unwrap() - 200.0 / 31.0).abs() < 0.01);
+    assert_eq!(
+        rows[3].start_date,
+        NaiveDate::from_ymd_opt(2022, 1, 11).unwrap()
+    );
+}
+
+#[test]
+fn run_to_string_returns_svg() {
+    let path = chart_file(CHART, "json5");
+    let svg = GanttChartTool::new(&TestLogger::default())
+        .run_to_string(["gantt", path.to_str().unwrap()].iter().map(Into::into))
+        .unwrap();
+
+    std::fs::remove_file(path).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(texts(&svg).contains(&"Test Chart".to_string()));
+}
+
+#[test]
+fn wrapped_title_has_a_line_each() {
+    let chart = CHART.replacen(
+        "\"Second\"",
+        "\"Second and much longer title that cannot fit\"",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &["--wrap-titles"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let lines = elements(&svg, "tspan");
+    let bars = with_class(&svg, "rect", "resource-0-closed");
+    let bar_middle = |bar: &HashMap<&str, &str>| value(bar["y"]) + value(bar["height"]) / 2.0;
+    let single_title_y = with_class(&svg, "text", "item")
+        .iter()
+        .find_map(|text| text.get("y").map(|y| value(y)))
+        .unwrap();
+
+    assert!(lines.len() > 1);
+
+    // The lines are as far from the middle of their bar as a single line is
+    let lines_middle = lines.iter().map(|line| value(line["y"])).sum::<f32>() / lines.len() as f32;
+
+    assert_eq!(
+        lines_middle - bar_middle(&bars[1]),
+        single_title_y - bar_middle(&bars[0])
+    );
+}
+
+#[test]
+fn tall_row_moves_later_rows_down() {
+    let log = TestLogger::default();
+    let chart = CHART.replacen(
+        "\"First\"",
+        "\"First and much longer title that cannot fit\"",
+        1,
+    );
+    let bar_ys = |svg: &str| -> Vec<f32> {
+        ["resource-0-closed", "resource-1-closed"]
+            .iter()
+            .flat_map(|class| with_class(svg, "rect", class))
+            .map(|bar| bar["y"].parse().unwrap())
+            .collect()
+    };
+    let ys = bar_ys(&run(&log, &chart, &[]).unwrap());
+    let wrapped_svg = run(&log, &chart, &["--wrap-titles"]).unwrap();
+    let wrapped_ys = bar_ys(&wrapped_svg);
+    let extra_height = (elements(&wrapped_svg, "tspan").len() - 1) as f32 * 20.0;
+
+    assert!(extra_height > 0.0);
+    // The first bar is in the middle of its taller row, and the rest are moved down by all of it
+    assert_eq!(wrapped_ys[0] - ys[0], extra_height / 2.0);
+    assert_eq!(wrapped_ys[1] - ys[1], extra_height);
+    assert_eq!(wrapped_ys[2] - ys[2], extra_height);
+}
+
+#[test]
+fn batch_inputs_with_same_name_fail() {
+    let dir = std::env::temp_dir().join(format!("gantt-test-{}-batch", std::process::id()));
+
+    std::fs::create_dir(&dir).unwrap();
+
+    let json5_path = dir.join("plan.json5");
+    let toml_path = dir.join("plan.toml");
+    let output_dir = dir.join("out");
+
+    std::fs::write(&json5_path, CHART).unwrap();
+    std::fs::write(&toml_path, "title = \"Test Chart\"").unwrap();
+    std::fs::create_dir(&output_dir).unwrap();
+
+    let result = GanttChartTool::new(&TestLogger::default()).run(
+        [
+            "gantt".as_ref(),
+            json5_path.as_os_str(),
+            toml_path.as_os_str(),
+            "--output-dir".as_ref(),
+            output_dir.as_os_str(),
+        ]
+        .iter()
+        .map(Into::into),
+    );
+    let written = std::fs::read_dir(&output_dir).unwrap().count();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(result.unwrap_err().to_string().starts_with(&format!(
+        "Input files '{}' and '{}' would both be written to '{}'",
+        json5_path.to_string_lossy(),
+        toml_path.to_string_lossy(),
+        output_dir.join("plan.svg").to_string_lossy()
+    )));
+    // Nothing is written before the clash is found
+    assert_eq!(written, 0);
+}
+
+#[test]
+fn centered_title_is_in_the_middle() {
+    let svg = run(
+        &TestLogger::default(),
+        &CHART.replacen('{', "{ subtitle: \"Phase One\",", 1),
+        &["--title-align", "center"],
+    )
+    .unwrap();
+    let width: f32 = elements(&svg, "svg")[0]["width"].parse().unwrap();
+
+    for class in ["title", "subtitle"] {
+        assert!(svg.contains(&format!(".{class}{{text-anchor:middle;}}")));
+        assert_eq!(
+            with_class(&svg, "text", class)[0]["x"]
+                .parse::<f32>()
+                .unwrap(),
+            width / 2.0
+        );
+    }
+}
+
+#[test]
+fn gzipped_chart_matches_original() {
+    use flate2::{
+        write::GzEncoder,
+        Compression,
+    };
+
+    let read_gzipped = |content: &str, extension: &str| {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+
+        encoder.write_all(content.as_bytes()).unwrap();
+
+        let path = chart_file("", extension);
+
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let chart_data = GanttChartTool::read_chart_file(
+            Cli::get_input(Some(&path)).unwrap(),
+            Cli::get_input_format(Some(&path)),
+        );
+
+        std::fs::remove_file(path).unwrap();
+        serde_json::to_value(chart_data.unwrap()).unwrap()
+    };
+    let original = serde_json::to_value(json5::from_str::<ChartData>(CHART).unwrap()).unwrap();
+
+    assert_eq!(read_gzipped(CHART, "json.gz"), original);
+    // The format is from the extension inside the .gz
+    assert_eq!(
+        read_gzipped(
+            "title = \"Test Chart\"\nresources = [\"Alice\", \"Bob\"]\nitems = []",
+            "toml.gz"
+        )["resources"],
+        original["resources"]
+    );
+}
+
+#[test]
+fn overlapping_resource_tasks_warn() {
+    let chart = |start_date: &str| {
+        format!(
+            r#"{{
+              title: "Test Chart",
+              resources: ["Alice", "Bob"],
+              items: [
+                {{ title: "Long", startDate: "2022-01-03", duration: 5, resource: 0 }},
+                {{ title: "Other", startDate: "2022-01-03", duration: 1, resource: 1 }},
+                {{ title: "Short", startDate: "{start_date}", duration: 2, resource: 0 }},
+              ],
+            }}"#
+        )
+    };
+    let log = TestLogger::default();
+
+    run(&log, &chart("2022-01-05"), &[]).unwrap();
+    assert_eq!(
+        *log.warnings.borrow(),
+        vec!["Resource 'Alice' is over-allocated from 2022-01-05 to 2022-01-07, 'Long' and 'Short' overlap"]
+    );
+    assert!(run(&log, &chart("2022-01-05"), &["--strict"]).is_err());
+
+    let log = TestLogger::default();
+
+    run(&log, &chart("2022-01-10"), &[]).unwrap();
+    assert!(log.warnings.borrow().is_empty());
+}
+
+#[test]
+fn strict_fails_on_warnings() {
+    let log = TestLogger::default();
+    let err = run(&log, WARNING_CHART, &["--strict"]).unwrap_err();
+
+    // Starting early, which is not counted again as overlapping on the one resource
+    assert!(err
+        .to_string()
+        .starts_with("1 warning(s) treated as errors with --strict"));
+    // The warnings themselves are still shown
+    assert_eq!(log.warnings.borrow().len(), 1);
+    assert!(run(&TestLogger::default(), CHART, &["--strict"]).is_ok());
+}
+
+#[test]
+fn custom_classes_and_styles_are_added() {
+    let chart = CHART.replacen(
+        "{ title: \"Third\",",
+        "{ title: \"Third\", classes: [\"release\", \"team-b\"],",
+        1,
+    );
+    let svg = run(
+        &TestLogger::default(),
+        &chart,
+        &["--extra-style", ".release{stroke:gold;}"],
+    )
+    .unwrap();
+    let bars = with_class(&svg, "rect", "release");
+
+    assert_eq!(bars.len(), 1);
+    assert_eq!(bars[0]["class"], "resource-1-closed release team-b");
+    assert_eq!(style_property(&svg, ".release", "stroke"), Some("gold"));
+}
+
+#[test]
+fn milestones_only_share_one_band() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Design Done" },
+        { title: "Second", duration: 3 },
+        { title: "Build Done" },
+      ],
+    }"#;
+    let args = ["--milestone-shape", "circle"];
+    let full = run(&TestLogger::default(), chart, &args).unwrap();
+    let svg = run(
+        &TestLogger::default(),
+        chart,
+        &[&args[..], &["--milestones-only"]].concat(),
+    )
+    .unwrap();
+    let circles = with_class(&svg, "circle", "milestone");
+    let height = |svg: &str| -> f32 { elements(svg, "svg")[0]["height"].parse().unwrap() };
+
+    assert!(with_class(&svg, "rect", "resource-0-closed").is_empty());
+    assert_eq!(circles.len(), 2);
+    assert_eq!(circles[0]["cy"], circles[1]["cy"]);
+    assert_ne!(circles[0]["cx"], circles[1]["cx"]);
+    // The titles go beside the milestones, as there is no title column
+    assert!(texts(&svg).contains(&"Build Done".to_string()));
+    // One band, where the full chart has four rows
+    assert_eq!(height(&full) - height(&svg), 3.0 * 30.0);
+
+    let error = run(&TestLogger::default(), CHART, &["--milestones-only"]).unwrap_err();
+
+    assert!(error
+        .to_string()
+        .starts_with("There are no milestones to show"));
+}
+
+#[test]
+fn hue_start_sets_first_resource_color() {
+    let colors = layout(
+        CHART,
+        &[
+            "--hue-start",
+            "0.0",
+            "--color-saturation",
+            "1",
+            "--color-value",
+            "1",
+        ],
+    )
+    .resource_colors();
+
+    // Pure red, with the next resource a golden ratio turn on from it
+    assert_eq!(colors[0].1, 0xff0000);
+    assert_ne!(colors[1].1, 0xff0000);
+
+    let cli = Cli::try_parse_from(["gantt", "--hue-start", "1.0"]).unwrap();
+
+    assert!(cli.layout_options().is_err());
+}
+
+#[test]
+fn show_bounds_labels_project_dates() {
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &["--show-bounds", "--date-format", "%Y-%m-%d"],
+    )
+    .unwrap();
+    let labels = with_class(&svg, "text", "axis-date");
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[0];
+
+    assert_eq!(labels.len(), 2);
+    // The start is under the first bar, and the end is the day after the last one
+    assert_eq!(labels[0]["x"], bar["x"]);
+    assert!(texts(&svg).contains(&"2022-01-03".to_string()));
+    assert!(texts(&svg).contains(&"2022-01-11".to_string()));
+    assert!(!texts(
+        &run(
+            &TestLogger::default(),
+            CHART,
+            &["--date-format", "%Y-%m-%d"]
+        )
+        .unwrap()
+    )
+    .contains(&"2022-01-03".to_string()));
+}
+
+#[test]
+fn milestone_start_date_sets_its_x() {
+    let chart = |placement: &str, duration: &str| {
+        format!(
+            r#"{{
+              title: "Test Chart",
+              resources: ["Alice"],
+              items: [
+                {{ title: "First", startDate: "2022-01-03", duration: 2, resource: 0 }},
+                {{ title: "Done", {placement}{duration} }},
+              ],
+            }}"#
+        )
+    };
+    let args = ["--milestone-shape", "circle"];
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+
+    for (placement, day) in [(r#"startDate: "2022-01-20""#, 19.0), ("lag: 2", 6.0)] {
+        let svg = run(&TestLogger::default(), &chart(placement, ""), &args).unwrap();
+        let task_svg = run(
+            &TestLogger::default(),
+            &chart(placement, ", duration: 1"),
+            &args,
+        )
+        .unwrap();
+        let circles = with_class(&svg, "circle", "milestone");
+
+        // Placed on its own date rather than at the end of the first task, just like a task
+        assert_eq!(circles.len(), 1);
+        assert!((value(circles[0]["cx"]) - (220.0 + day * 200.0 / 31.0)).abs() < 0.02);
+        assert_eq!(
+            circles[0]["cx"],
+            with_class(&task_svg, "rect", "resource-0-closed")[1]["x"]
+        );
+    }
+}
+
+#[test]
+fn margins_size_and_offset_chart() {
+    let log = TestLogger::default();
+    let svg = run(&log, CHART, &[]).unwrap();
+    let margin_svg = run(
+        &log,
+        CHART,
+        &[
+            "--margin-top",
+            "20",
+            "--margin-left",
+            "30",
+            "--margin-right",
+            "40",
+            "--margin-bottom",
+            "50",
+        ],
+    )
+    .unwrap();
+    let size = |svg: &str, name: &str| -> f32 { elements(svg, "svg")[0][name].parse().unwrap() };
+    let bar = |svg: &str, name: &str| -> f32 {
+        with_class(svg, "rect", "resource-0-closed")[0][name]
+            .parse()
+            .unwrap()
+    };
+
+    assert_eq!(
+        size(&margin_svg, "width") - size(&svg, "width"),
+        20.0 + 30.0
+    );
+    assert_eq!(
+        size(&margin_svg, "height") - size(&svg, "height"),
+        20.0 + 40.0
+    );
+    assert!((bar(&margin_svg, "x") - bar(&svg, "x") - 20.0).abs() < 0.02);
+    assert_eq!(bar(&margin_svg, "y") - bar(&svg, "y"), 20.0);
+
+    let cli = Cli::try_parse_from(["gantt", "--margin-left=-1"]).unwrap();
+
+    assert!(cli
+        .layout_options()
+        .unwrap_err()
+        .to_string()
+        .starts_with("Margins must not be negative"));
+}
+
+#[test]
+fn seeded_charts_are_identical() {
+    let svg = |args: &[&str]| run(&TestLogger::default(), CHART, args).unwrap();
+    let seeded = svg(&["--seed", "42"]);
+
+    assert_eq!(seeded, svg(&["--seed", "42"]));
+    assert_ne!(
+        layout(CHART, &["--seed", "42"]).resource_colors(),
+        layout(CHART, &["--seed", "43"]).resource_colors()
+    );
+
+    // Only the numbers are rounded, not the text
+    let precise = svg(&["--seed", "42", "--precision", "4"]);
+    let bar = |svg: &str| with_class(svg, "rect", "resource-0-closed")[0]["x"].to_string();
+
+    assert_eq!(bar(&seeded), "232.9");
+    assert_eq!(bar(&precise), "232.9032");
+    assert!(texts(&precise).contains(&"Test Chart".to_string()));
+}
+
+#[test]
+fn phase_band_spans_its_group() {
+    let chart = CHART
+        .replacen(
+            "{ title: \"First\",",
+            "{ title: \"First\", group: \"Design\",",
+            1,
+        )
+        .replacen(
+            "{ title: \"Second\",",
+            "{ title: \"Second\", group: \"Design\",",
+            1,
+        )
+        .replacen(
+            "{ title: \"Third\",",
+            "{ title: \"Third\", group: \"Build\",",
+            1,
+        );
+    let svg = run(&TestLogger::default(), &chart, &["--phase-bands"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let bars = with_class(&svg, "rect", "resource-0-closed");
+    let design = &with_class(&svg, "rect", "phase-0")[0];
+    let build = &with_class(&svg, "rect", "phase-1")[0];
+    let third = &with_class(&svg, "rect", "resource-1-closed")[0];
+
+    // From the start of the first item to the end of the second
+    assert_eq!(design["x"], bars[0]["x"]);
+    assert!(
+        (value(design["x"]) + value(design["width"])
+            - (value(bars[1]["x"]) + value(bars[1]["width"])))
+        .abs()
+            < 0.02
+    );
+    assert_eq!(build["x"], third["x"]);
+    assert_eq!(build["width"], third["width"]);
+    // Above the rows
+    assert!(value(design["y"]) < value(bars[0]["y"]));
+    assert!(texts(&svg).contains(&"Design".to_string()));
+}
+
+#[test]
+fn top_legend_adds_no_band() {
+    let svg = |position: &str| {
+        run(
+            &TestLogger::default(),
+            CHART,
+            &["--legend-position", position],
+        )
+        .unwrap()
+    };
+    let height = |svg: &str| -> f32 { elements(svg, "svg")[0]["height"].parse().unwrap() };
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let (top, bottom, none) = (svg("top"), svg("bottom"), svg("none"));
+
+    assert_eq!(height(&top), height(&none));
+    assert!(height(&bottom) > height(&none));
+    assert!(with_class(&none, "text", "legend-entry").is_empty());
+
+    // On the title line, to the right of the title
+    let title = &with_class(&top, "text", "title")[0];
+    let entries = with_class(&top, "text", "legend-entry");
+
+    assert_eq!(entries.len(), 2);
+
+    for entry in entries.iter() {
+        assert!((value(entry["y"]) - value(title["y"])).abs() < 10.0);
+        assert!(value(entry["x"]) > value(title["x"]));
+    }
+}
+
+#[test]
+fn blocked_status_marks_bar_end() {
+    let chart = CHART.replacen(
+        "{ title: \"Second\",",
+        "{ title: \"Second\", status: \"blocked\",",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let bar = &with_class(&svg, "rect", "resource-0-closed")[1];
+    let markers = with_class(&svg, "text", "status-blocked");
+
+    // Just past the end of the bar, in the middle of its row
+    assert_eq!(markers.len(), 1);
+    assert!(texts(&svg).contains(&"✗".to_string()));
+    assert!(
+        (value(markers[0]["x"]) - (value(bar["x"]) + value(bar["width"]) + 5.0 + 6.0)).abs() < 0.02
+    );
+    assert_eq!(
+        value(markers[0]["y"]),
+        value(bar["y"]) + value(bar["height"]) / 2.0
+    );
+
+    // Anything else is a dot
+    let svg = run(
+        &TestLogger::default(),
+        &chart.replace("blocked", "waiting"),
+        &[],
+    )
+    .unwrap();
+
+    assert_eq!(with_class(&svg, "circle", "status-waiting").len(), 1);
+}
+
+#[test]
+fn marked_date_label_is_beside_line() {
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let chart = CHART.replacen(
+        '{',
+        "{ markedDate: { date: \"2022-01-05\", label: \"Review\" },",
+        1,
+    );
+    let svg = run(&TestLogger::default(), &chart, &[]).unwrap();
+    let line = &with_class(&svg, "line", "marker")[0];
+    let labels = with_class(&svg, "text", "marker-label");
+
+    assert_eq!(labels.len(), 1);
+    assert!(texts(&svg).contains(&"Review".to_string()));
+    assert!((value(labels[0]["x"]) - value(line["x1"]) - 4.0).abs() < 0.02);
+    // On the same date as the chart with the plain date
+    let plain_svg = run(
+        &TestLogger::default(),
+        &CHART.replacen('{', "{ markedDate: \"2022-01-05\",", 1),
+        &[],
+    )
+    .unwrap();
+
+    assert_eq!(
+        with_class(&plain_svg, "line", "marker")[0]["x1"],
+        line["x1"]
+    );
+    assert!(with_class(&plain_svg, "text", "marker-label").is_empty());
+}
+
+#[test]
+fn calendar_days_count_the_weekend() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice"],
+      items: [
+        { title: "Rental", startDate: "2022-01-07", duration: 2, resource: 0 },
+        { title: "Return", duration: 1 },
+      ],
+    }"#;
+    let bars = |args: &[&str]| -> Vec<(f32, f32)> {
+        with_class(
+            &run(&TestLogger::default(), chart, args).unwrap(),
+            "rect",
+            "resource-0-closed",
+        )
+        .iter()
+        .map(|bar| (bar["x"].parse().unwrap(), bar["width"].parse().unwrap()))
+        .collect()
+    };
+    let day_width = 200.0 / 31.0;
+
+    // Ending on the Sunday rather than being carried over to the Monday
+    for flag in ["--calendar-days", "--no-weekend-adjust"] {
+        let calendar_bars = bars(&[flag]);
+
+        assert!((calendar_bars[0].1 - 2.0 * day_width).abs() < 0.02);
+        assert!((calendar_bars[1].0 - (220.0 + 8.0 * day_width)).abs() < 0.02);
+    }
+
+    assert!((bars(&[])[0].1 - 3.0 * day_width).abs() < 0.02);
+}
+
+#[test]
+fn emit_writes_each_variant() {
+    let input = chart_file(CHART, "json5");
+    let dir = std::env::temp_dir().join(format!("gantt-test-emit-{}", std::process::id()));
+    let emit = |variants: &str| {
+        GanttChartTool::new(&TestLogger::default()).run_cli(
+            &Cli::try_parse_from([
+                "gantt",
+                input.to_str().unwrap(),
+                dir.join("chart.svg").to_str().unwrap(),
+                "--emit",
+                variants,
+            ])
+            .unwrap(),
+        )
+    };
+    let file_names = || -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    };
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    #[cfg(feature = "png")]
+    {
+        emit("svg,png@1x,png@2x").unwrap();
+        assert_eq!(file_names(), ["chart.png", "chart.svg", "chart@2x.png"]);
+    }
+
+    #[cfg(not(feature = "png"))]
+    {
+        emit("svg").unwrap();
+        assert_eq!(file_names(), ["chart.svg"]);
+        assert!(emit("svg,png@2x").is_err());
+    }
+
+    assert!(emit("svg,jpeg")
+        .unwrap_err()
+        .to_string()
+        .starts_with("Unknown output 'jpeg', expected svg, png or png@SCALEx such as png@2x"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_file(input).unwrap();
+}
+
+#[test]
+fn slack_runs_to_what_follows() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Second", duration: 3 },
+        { title: "Short", startDate: "2022-01-04", duration: 1, resource: 1 },
+        { title: "Third", startDate: "2022-01-12", duration: 1, resource: 1 },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &["--show-slack"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let slack = with_class(&svg, "rect", "slack");
+    let day_width = 200.0 / 31.0;
+    let render_data = layout(chart, &[]);
+
+    // The first is followed on from straight away and the third is critical, so only the
+    // second and short items can slip, up to the end of the project on the 13th
+    assert_eq!(
+        render_data
+            .rows
+            .iter()
+            .map(|row| row.slack_days)
+            .collect::<Vec<_>>(),
+        [0, 3, 8, 0]
+    );
+    assert_eq!(slack.len(), 2);
+    assert!((value(slack[0]["width"]) - 3.0 * day_width).abs() < 0.02);
+    assert!((value(slack[1]["width"]) - 8.0 * day_width).abs() < 0.02);
+    assert!(with_class(
+        &run(&TestLogger::default(), chart, &[]).unwrap(),
+        "rect",
+        "slack"
+    )
+    .is_empty());
+}
+
+#[test]
+fn title_divider_and_task_header_can_be_left_out() {
+    let svg = |args: &[&str]| run(&TestLogger::default(), CHART, args).unwrap();
+    let has_divider = |svg: &str| vertical_line_xs(svg).contains(&220.0);
+    let has_header = |svg: &str| texts(svg).contains(&"Tasks".to_string());
+    let (plain, no_divider, no_header) = (
+        svg(&[]),
+        svg(&["--no-title-divider"]),
+        svg(&["--no-task-header"]),
+    );
+
+    assert!(has_divider(&plain) && has_header(&plain));
+    assert!(!has_divider(&no_divider) && has_header(&no_divider));
+    assert!(has_divider(&no_header) && !has_header(&no_header));
+    // The months keep their headings
+    assert!(texts(&no_header).contains(&"Jan".to_string()));
+}
+
+#[test]
+fn resource_name_matches_index() {
+    let named_chart = CHART
+        .replacen("resource: 0", "resource: \"Alice\"", 1)
+        .replacen("resource: 1", "resource: \"Bob\"", 1);
+    let resource_indices = |chart: &str| -> Vec<usize> {
+        layout(chart, &["--seed", "7"])
+            .rows
+            .iter()
+            .map(|row| row.resource_index)
+            .collect()
+    };
+
+    assert_eq!(resource_indices(&named_chart), [0, 0, 1]);
+    assert_eq!(
+        run(&TestLogger::default(), &named_chart, &["--seed", "7"]).unwrap(),
+        run(&TestLogger::default(), CHART, &["--seed", "7"]).unwrap()
+    );
+
+    let err = run(
+        &TestLogger::default(),
+        &named_chart.replace("resource: \"Bob\"", "resource: \"Carol\""),
+        &[],
+    )
+    .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("Item 'Third' resource 'Carol' is not one of the resources"));
+}
+
+#[test]
+fn html_wraps_the_svg() {
+    let chart = CHART.replacen("Test Chart", "Plans <Q1>", 1);
+    let svg = run(&TestLogger::default(), &chart, &["--seed", "3"]).unwrap();
+    let html = run(&TestLogger::default(), &chart, &["--seed", "3", "--html"]).unwrap();
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>Plans &lt;Q1&gt;</title>"));
+    assert!(html.contains(&svg));
+    assert_eq!(
+        run(
+            &TestLogger::default(),
+            &chart,
+            &["--seed", "3", "--format", "html"]
+        )
+        .unwrap(),
+        html
+    );
+}
+
+#[test]
+fn item_colors_are_checked_and_expanded() {
+    let colored = |color: &str| {
+        CHART.replacen(
+            "{ title: \"Second\",",
+            &format!("{{ title: \"Second\", color: \"{color}\","),
+            1,
+        )
+    };
+
+    for bad_color in ["#gggggg", "#12", "notacolor"] {
+        let err = run(&TestLogger::default(), &colored(bad_color), &[]).unwrap_err();
+
+        assert!(err.to_string().contains(&format!(
+            "Item 'Second' has an invalid color '{bad_color}', expected #rgb, #rrggbb or a CSS color name"
+        )));
+    }
+
+    let styles = layout(&colored("#f80"), &[]).styles;
+
+    assert!(styles
+        .contains(&".item-1-closed{stroke-width:1; stroke:#ff8800; fill:#ff8800;}".to_string()));
+    assert_eq!(colors::parse("Red"), Some(0xff0000));
+    assert_eq!(colors::parse("#AbC"), Some(0xaabbcc));
+}
+
+#[test]
+fn weekend_marks_count_the_weekend_days() {
+    let marks = |args: &[&str]| {
+        with_class(
+            &run(&TestLogger::default(), CHART, args).unwrap(),
+            "rect",
+            "weekend-mark",
+        )
+        .len()
+    };
+
+    // January 2022 starts on a Saturday and has five weekends
+    assert_eq!(marks(&["--show-weekends-in-header"]), 10);
+    assert_eq!(
+        marks(&[
+            "--show-weekends-in-header",
+            "--window",
+            "2022-01-05..2022-01-20"
+        ]),
+        4
+    );
+    assert_eq!(marks(&[]), 0);
+}
+
+#[test]
+fn lead_days_move_first_task_right() {
+    let svg = |args: &[&str]| run(&TestLogger::default(), CHART, args).unwrap();
+    let first_x = |svg: &str| -> f32 {
+        with_class(svg, "rect", "resource-0-closed")[0]["x"]
+            .parse()
+            .unwrap()
+    };
+    let lead_svg = svg(&["--lead-days", "10"]);
+
+    // December has as many days as January, so each day of the lead is as wide
+    assert!((first_x(&lead_svg) - first_x(&svg(&[])) - 10.0 * 200.0 / 31.0).abs() < 0.02);
+    assert!(texts(&lead_svg).contains(&"Dec".to_string()));
+
+    // The December heading sits in the middle of the lead, not over January
+    let dec_x: f32 = with_class(&lead_svg, "text", "heading")[0]["x"]
+        .parse()
+        .unwrap();
+
+    assert!((dec_x - (220.0 + 5.0 * 200.0 / 31.0)).abs() < 0.02);
+
+    let err = run(
+        &TestLogger::default(),
+        CHART,
+        &["--lead-days", "4000000000"],
+    )
+    .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .starts_with("A lead of 4000000000 days is out of range"));
+}
+
+#[test]
+fn month_name_callback_names_columns() {
+    let path = chart_file(CHART, "json5");
+    let month_name = |year: i32, month: u32| format!("{} {year}", MONTH_NAMES[month as usize - 1]);
+    let svg = GanttChartTool::new(&TestLogger::default())
+        .with_month_name(&month_name)
+        .run_to_string(["gantt", path.to_str().unwrap()].iter().map(Into::into))
+        .unwrap();
+
+    std::fs::remove_file(path).unwrap();
+    assert!(texts(&svg).contains(&"Jan 2022".to_string()));
+    assert!(!texts(&svg).contains(&"Jan".to_string()));
+}
+
+#[test]
+fn max_height_shows_a_window_from_the_scrolled_row() {
+    let svg = |args: &[&str]| run(&TestLogger::default(), CHART, args).unwrap();
+    let full = svg(&[]);
+    let document = |svg: &str| {
+        let attributes = &elements(svg, "svg")[0];
+
+        (
+            attributes["height"].to_string(),
+            attributes["viewBox"].to_string(),
+        )
+    };
+    let width = &elements(&full, "svg")[0]["width"];
+
+    // The second row is where the first would be, the width is the same
+    assert_eq!(
+        document(&svg(&["--max-height", "100", "--scroll-to", "1"])),
+        ("100".to_string(), format!("0 30 {width} 100"))
+    );
+    assert_eq!(
+        document(&svg(&["--max-height", "100"])),
+        ("100".to_string(), format!("0 0 {width} 100"))
+    );
+    // A cap taller than the chart changes nothing
+    assert_eq!(document(&svg(&["--max-height", "1000"])), document(&full));
+
+    let err = run(
+        &TestLogger::default(),
+        CHART,
+        &["--max-height", "100", "--scroll-to", "3"],
+    )
+    .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .starts_with("Cannot scroll to row 3, there are only 3 rows"));
+}
+
+#[test]
+fn utilization_stacks_overlapping_tasks() {
+    let chart = r#"{
+      title: "Test Chart",
+      resources: ["Alice", "Bob"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Other", startDate: "2022-01-04", duration: 1, resource: 1 },
+      ],
+    }"#;
+    let svg = run(&TestLogger::default(), chart, &["--utilization"]).unwrap();
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    // The bars are taller than a step, and on the 4th both tasks are going
+    let steps = |day: f32| -> Vec<(String, f32, f32)> {
+        ["resource-0-closed", "resource-1-closed"]
+            .iter()
+            .flat_map(|class| with_class(&svg, "rect", class))
+            .filter(|rect| {
+                value(rect["height"]) < 20.0
+                    && (value(rect["x"]) - (220.0 + day * 200.0 / 31.0)).abs() < 0.02
+            })
+            .map(|rect| {
+                (
+                    rect["class"].to_string(),
+                    value(rect["y"]),
+                    value(rect["height"]),
+                )
+            })
+            .collect()
+    };
+    let overlap = steps(3.0);
+
+    assert_eq!(overlap.len(), 2);
+    assert_eq!(
+        overlap.iter().map(|(_, _, height)| height).sum::<f32>(),
+        12.0
+    );
+    // One on top of the other
+    assert_eq!(overlap[0].1 - overlap[1].1, 6.0);
+    assert_eq!(steps(2.0).len(), 1);
+    assert!(steps(5.0).is_empty());
+}
+
+#[test]
+fn auto_status_marks_overdue_and_done() {
+    let chart = CHART
+        .replacen(
+            "{ title: \"First\",",
+            "{ title: \"First\", percentComplete: 100,",
+            1,
+        )
+        .replacen(
+            "{ title: \"Second\",",
+            "{ title: \"Second\", percentComplete: 0,",
+            1,
+        );
+    let args = ["--auto-status", "--today", "2022-01-12"];
+    let svg = run(&TestLogger::default(), &chart, &args).unwrap();
+    let overdue = with_class(&svg, "rect", "overdue");
+    let render_data = layout(&chart, &args);
+
+    // The second and third tasks ended before the 12th without being finished
+    assert_eq!(overdue.len(), 2);
+    assert_eq!(
+        overdue[0]["x"],
+        with_class(&svg, "rect", "resource-0-closed")[1]["x"]
+    );
+    assert_eq!(render_data.rows[0].status.as_deref(), Some("done"));
+    assert!(render_data
+        .styles
+        .contains(&".overdue{stroke:#d62728; fill:#d62728;}".to_string()));
+
+    // Nothing is overdue before the tasks end, and the style is only there when needed
+    assert!(with_class(
+        &run(
+            &TestLogger::default(),
+            &chart,
+            &["--auto-status", "--today", "2022-01-04"]
+        )
+        .unwrap(),
+        "rect",
+        "overdue"
+    )
+    .is_empty());
+    assert!(!layout(&chart, &[])
+        .styles
+        .iter()
+        .any(|style| style.starts_with(".overdue")));
+}
+
+#[test]
+fn max_title_chars_truncates_with_tooltip() {
+    let chart = CHART.replacen("\"Second\"", "\"A very long task title\"", 1);
+    let svg = run(&TestLogger::default(), &chart, &["--max-title-chars", "10"]).unwrap();
+
+    // Ten characters with the ellipsis, the row is no taller, and the short titles are left alone
+    assert!(svg.contains("A very lo…\n<title>A very long task title</title>\n</text>"));
+    assert_eq!("A very lo…".chars().count(), 10);
+    assert!(texts(&svg).contains(&"Third".to_string()));
+    assert_eq!(
+        elements(&svg, "svg")[0]["height"],
+        elements(&run(&TestLogger::default(), &chart, &[]).unwrap(), "svg")[0]["height"]
+    );
+
+    let cli = Cli::try_parse_from(["gantt", "--max-title-chars", "0"]).unwrap();
+
+    assert!(cli.render_options().is_err());
+}
+
+#[test]
+fn dump_styles_prints_each_rule() {
+    let log = TestLogger::default();
+
+    assert_eq!(
+        run(&log, CHART, &["--dump-styles", "--class-prefix", "g-"]).unwrap(),
+        ""
+    );
+
+    let output = log.output.borrow();
+
+    // One rule a line, with the classes left for --extra-style to prefix
+    assert!(output.iter().any(|rule| rule.starts_with(".milestone{")));
+    assert!(output
+        .iter()
+        .any(|rule| rule.starts_with(".resource-0-closed{")));
+    assert!(output.iter().all(|rule| !rule.contains('\n')));
+
+    // The dumped rules go back in with the prefix added just the once
+    let mut args = vec!["--class-prefix", "g-"];
+
+    for rule in output.iter() {
+        args.extend(["--extra-style", rule]);
+    }
+
+    let svg = run(&TestLogger::default(), CHART, &args).unwrap();
+
+    assert!(svg.contains(".g-milestone{"));
+    assert!(!svg.contains(".g-g-"));
+}
+
+#[test]
+fn weeks_expand_to_working_days() {
+    let chart = r#"{
+      title: "Test Chart",
+      durationUnit: "weeks",
+      resources: ["Alice"],
+      items: [
+        { title: "First", startDate: "2022-01-03", duration: 2, resource: 0 },
+        { title: "Second", duration: 3, durationUnit: "days" },
+      ],
+    }"#;
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let svg = run(&TestLogger::default(), chart, &["--show-duration"]).unwrap();
+    let bars = with_class(&svg, "rect", "resource-0-closed");
+    let day_width = 200.0 / 31.0;
+
+    // Two weekends in the two weeks, then the item in days carries on from the Monday
+    assert!((value(bars[0]["width"]) - 14.0 * day_width).abs() < 0.02);
+    assert!((value(bars[1]["x"]) - (220.0 + 16.0 * day_width)).abs() < 0.02);
+    assert!((value(bars[1]["width"]) - 3.0 * day_width).abs() < 0.02);
+    assert!(texts(&svg).contains(&"17 calendar days, 13 working days".to_string()));
+}
+
+#[test]
+fn month_headings_are_centered_on_their_columns() {
+    let value = |s: &str| -> f32 { s.parse().unwrap() };
+    let svg = run(
+        &TestLogger::default(),
+        CHART,
+        &["--window", "2022-01-24..2022-02-03"],
+    )
+    .unwrap();
+    let width = value(elements(&svg, "svg")[0]["width"]);
+    let headings: Vec<f32> = with_class(&svg, "text", "heading")
+        .iter()
+        .filter(|heading| !heading["class"].contains("task-heading"))
+        .map(|heading| value(heading["x"]))
+        .collect();
+    let day_width = 200.0 / 31.0;
+
+    // Eight days of January and three of February, each heading in the middle of its part
+    assert_eq!(headings.len(), 2);
+    assert!(headings.iter().all(|x| *x < width));
+    assert!((headings[0] - (220.0 + 4.0 * day_width)).abs() < 0.02);
+    assert!((headings[1] - (220.0 + 8.0 * day_width + 1.5 * day_width)).abs() < 0.02);
+}